@@ -0,0 +1,375 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use clap::{value_parser, App, Arg, ArgGroup};
+use clap_complete::Shell;
+use roff::{bold, italic, roman, Roff};
+
+use crate::config::{is_address, TEMPLATE_COMMANDS};
+
+/// Name the completion scripts and man page refer to the binary by
+pub const BIN_NAME: &str = "deluxe_stat_bot";
+
+/// Utility fn to validate an --address value
+fn parse_address(value: &str) -> Result<String, String> {
+    match is_address(value) {
+        true => Ok(String::from(value)),
+        false => Err(String::from("expected a 0x prefixed 20 byte address")),
+    }
+}
+
+/// Utility fn to validate a --scale value
+fn parse_scale(value: &str) -> Result<f64, String> {
+    match f64::from_str(value) {
+        Ok(x) if x > 0.0 && x.is_finite() => Ok(x),
+        Ok(_) => Err(String::from("must be above 0")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// --collection / --address pair naming the contract a command works on, one of them is required
+fn target_args() -> [Arg<'static>; 2] {
+    [
+        Arg::new("collection")
+            .long("collection")
+            .short('n')
+            .takes_value(true)
+            .value_name("NAME")
+            .help("Collection listed in the collections config"),
+        Arg::new("address")
+            .long("address")
+            .short('a')
+            .takes_value(true)
+            .value_name("ADDRESS")
+            .value_parser(parse_address)
+            .help("Any ERC721 contract address, instead of a configured collection"),
+    ]
+}
+
+/// --lookback-days of the commands charting daily mints
+fn lookback_arg() -> Arg<'static> {
+    Arg::new("lookback")
+        .long("lookback-days")
+        .short('l')
+        .takes_value(true)
+        .value_name("DAYS")
+        .value_parser(value_parser!(u64).range(0..=180))
+        .default_value("0")
+        .help("Days to chart, up to 180, 0 charts the full history")
+}
+
+/// --output and size of a chart, overriding the charts section of the config
+fn chart_args() -> [Arg<'static>; 4] {
+    [
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Chart file, html, png, svg or jpeg by extension, defaults to <output.dir>/<name>_mint_activity.<charts.format>"),
+        Arg::new("width")
+            .long("width")
+            .takes_value(true)
+            .value_name("PX")
+            .value_parser(value_parser!(u64).range(1..))
+            .help("Chart width, overrides charts.width"),
+        Arg::new("height")
+            .long("height")
+            .takes_value(true)
+            .value_name("PX")
+            .value_parser(value_parser!(u64).range(1..))
+            .help("Chart height, overrides charts.height"),
+        Arg::new("scale")
+            .long("scale")
+            .takes_value(true)
+            .value_name("FACTOR")
+            .value_parser(parse_scale)
+            .help("Image scale factor, overrides charts.scale"),
+    ]
+}
+
+/// --post / --dry-run of the commands that publish a status
+fn publish_args() -> [Arg<'static>; 4] {
+    [
+        Arg::new("post")
+            .long("post")
+            .short('p')
+            .help("Publish the status to the publishers of the collection, twitter if it lists none; charts must be png or jpeg"),
+        Arg::new("dry_run")
+            .long("dry-run")
+            .help("Write the post to the outbox instead of publishing it, with its length check"),
+        Arg::new("outbox")
+            .long("outbox")
+            .takes_value(true)
+            .value_name("DIR")
+            .requires("dry_run")
+            .help("Outbox directory, overrides output.outbox"),
+        Arg::new("force")
+            .long("force")
+            .requires("post")
+            .help("Post even when the ledger holds the post back as unchanged or too soon"),
+    ]
+}
+
+/// Requires exactly one of the target_args
+fn target_group() -> ArgGroup<'static> {
+    ArgGroup::new("target")
+        .args(&["collection", "address"])
+        .required(true)
+}
+
+/// Builds the command line definition, shared by parse_args and anything generating docs from it
+pub fn build_cli() -> App<'static> {
+    App::new(BIN_NAME)
+        .about("Mint, migration and holder stats for ERC721 collections")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .args(&[
+            Arg::new("conf")
+                .long("config")
+                .short('c')
+                .takes_value(true)
+                .value_name("FILE")
+                .default_value("config.yaml")
+                .global(true)
+                .help("Configuration file"),
+            Arg::new("source")
+                .long("source")
+                .short('s')
+                .takes_value(true)
+                .value_name("SOURCE")
+                .possible_values(["etherscan", "rpc"])
+                .default_value("etherscan")
+                .global(true)
+                .help("Where transfers are fetched from"),
+            Arg::new("input")
+                .long("input")
+                .takes_value(true)
+                .value_name("FILE")
+                .global(true)
+                .help("Read transfers from an exported dataset instead of --source"),
+            Arg::new("log_file")
+                .long("log-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .global(true)
+                .help("Log file, overrides logging.file of the config"),
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .multiple_occurrences(true)
+                .conflicts_with("quiet")
+                .global(true)
+                .help("Log more to stderr, -vv for trace"),
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .global(true)
+                .help("Only log errors to stderr"),
+        ])
+        .subcommand(App::new("mint-activity")
+            .about("Chart the daily mint activity of a collection or contract")
+            .args(target_args())
+            .group(target_group())
+            .arg(lookback_arg())
+            .args(chart_args())
+            .args(publish_args()))
+        .subcommand(App::new("migration")
+            .about("Report the migration progress of a collection against its max supply")
+            .arg(Arg::new("collection")
+                .long("collection")
+                .short('n')
+                .takes_value(true)
+                .value_name("NAME")
+                .default_value("bears")
+                .help("Collection listed in the collections config"))
+            .args(publish_args()))
+        .subcommand(App::new("holders")
+            .about("List the current holders of a collection or contract")
+            .args(target_args())
+            .group(target_group())
+            .arg(Arg::new("top")
+                .long("top")
+                .short('t')
+                .takes_value(true)
+                .value_name("N")
+                .value_parser(value_parser!(u64).range(1..))
+                .default_value("10")
+                .help("Number of holders to list")))
+        .subcommand(App::new("export")
+            .about("Write the transfer history and daily mint stats of a collection or contract")
+            .args(target_args())
+            .group(target_group())
+            .arg(Arg::new("output")
+                .long("output")
+                .short('o')
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Dataset file, defaults to <output.dir>/<name>.<ext> of the config"))
+            .arg(Arg::new("format")
+                .long("format")
+                .short('f')
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(["csv", "json", "ndjson", "jsonl", "parquet"])
+                .help("Dataset format, defaults to the --output extension or output.format")))
+        .subcommand(App::new("render-template")
+            .about("Preview the status a command would post, rendered from its template")
+            .arg(Arg::new("command")
+                .required(true)
+                .value_name("COMMAND")
+                .possible_values(TEMPLATE_COMMANDS)
+                .help("Command whose status is rendered"))
+            .args(target_args())
+            .group(target_group())
+            .arg(lookback_arg())
+            .arg(Arg::new("template")
+                .long("template")
+                .short('t')
+                .takes_value(true)
+                .value_name("TEXT")
+                .help("Template to render instead of the configured one"))
+            .arg(Arg::new("template_file")
+                .long("template-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with("template")
+                .help("Read the template to render from a file"))
+            .arg(Arg::new("vars")
+                .long("vars")
+                .help("Also print the template variables as json")))
+        .subcommand(App::new("history")
+            .about("List the posts recorded in the ledger, latest first")
+            .arg(Arg::new("collection")
+                .long("collection")
+                .short('n')
+                .takes_value(true)
+                .value_name("NAME")
+                .help("Only the posts of this collection, or address"))
+            .arg(Arg::new("limit")
+                .long("limit")
+                .takes_value(true)
+                .value_name("N")
+                .value_parser(value_parser!(u64).range(1..))
+                .default_value("20")
+                .help("Number of posts to list")))
+        .subcommand(App::new("pipeline")
+            .about("Run a pipeline of the pipelines section, its stages in dependency order")
+            .arg(Arg::new("name")
+                .required_unless_present("list")
+                .value_name("NAME")
+                .help("Name of the pipeline"))
+            .arg(Arg::new("list")
+                .long("list")
+                .conflicts_with("name")
+                .help("Print every pipeline with its stages in the order they run, then exit"))
+            .args(publish_args()))
+        .subcommand(App::new("serve")
+            .about("Serve the collection stats as json over http from the cache, until interrupted")
+            .arg(Arg::new("bind")
+                .long("bind")
+                .short('b')
+                .takes_value(true)
+                .value_name("ADDR")
+                .value_parser(value_parser!(std::net::SocketAddr))
+                .help("ip:port to listen on, overrides server.bind")))
+        .subcommand(App::new("run")
+            .about("Run the jobs of the schedule section at their times, until interrupted")
+            .arg(Arg::new("list")
+                .long("list")
+                .help("Print every job with its next run time, then exit"))
+            .arg(Arg::new("job")
+                .long("job")
+                .short('j')
+                .takes_value(true)
+                .value_name("NAME")
+                .conflicts_with("list")
+                .help("Run this job once now, then exit")))
+        .subcommand(App::new("completions")
+            .about("Print a shell completion script")
+            .arg(Arg::new("shell")
+                .required(true)
+                .value_name("SHELL")
+                .value_parser(value_parser!(Shell))
+                .help("Shell to complete for")))
+        .subcommand(App::new("manpage")
+            .about("Print the man page in roff format"))
+}
+
+/// Utility fn to write the completion script of a shell, e.g. for ~/.local/share/bash-completion
+pub fn write_completions(shell: Shell, out: &mut dyn Write) {
+    clap_complete::generate(shell, &mut build_cli(), BIN_NAME, out);
+}
+
+/// Utility fn to add one .TP entry per option, e.g. "-c, --config <FILE>" and its help
+fn roff_args<'a, 'help: 'a>(page: &mut Roff, args: impl Iterator<Item = &'a Arg<'help>>) {
+    for arg in args {
+        let mut flag = vec![];
+        if let Some(short) = arg.get_short() {
+            flag.push(bold(format!("-{}", short)));
+        }
+        if let Some(long) = arg.get_long() {
+            if !flag.is_empty() { flag.push(roman(", ")); }
+            flag.push(bold(format!("--{}", long)));
+        }
+        if let Some(names) = arg.get_value_names().filter(|_| arg.is_takes_value_set()) {
+            for name in names {
+                if !flag.is_empty() { flag.push(roman(" ")); }
+                flag.push(italic(format!("<{}>", name)));
+            }
+        }
+
+        let mut help = String::from(arg.get_help().unwrap_or(""));
+        let possible: Vec<String> = match arg.get_possible_values() {
+            Some(x) => x.iter().map(|x| String::from(x.get_name())).collect(),
+            None => arg.get_value_parser()
+                .possible_values()
+                .map(|x| x.map(|x| String::from(x.get_name())).collect())
+                .unwrap_or_default(),
+        };
+        if !possible.is_empty() {
+            help.push_str(&format!(" [possible values: {}]", possible.join(", ")));
+        }
+        if !arg.get_default_values().is_empty() {
+            let defaults: Vec<_> = arg.get_default_values().iter().map(|x| x.to_string_lossy()).collect();
+            help.push_str(&format!(" [default: {}]", defaults.join(", ")));
+        }
+
+        page.control("TP", []).text(flag).text([roman(help)]);
+    }
+}
+
+/// Utility fn to render the man page from the command line definition, in roff format
+pub fn render_manpage() -> String {
+    let mut cli = build_cli();
+    cli.build();
+
+    let mut page = Roff::new();
+    page.control("TH", [BIN_NAME.to_uppercase().as_str(), "1"])
+        .control("SH", ["NAME"])
+        .text([roman(format!("{} - {}", BIN_NAME, cli.get_about().unwrap_or("")))])
+        .control("SH", ["SYNOPSIS"])
+        .text([bold(BIN_NAME), roman(" [OPTIONS] "), italic("<SUBCOMMAND>")])
+        .control("SH", ["OPTIONS"])
+        .text([roman("Accepted before or after any subcommand.")]);
+    roff_args(&mut page, cli.get_arguments().filter(|x| !x.is_hide_set()));
+
+    page.control("SH", ["SUBCOMMANDS"]);
+    let names: Vec<String> = cli.get_subcommands()
+        .map(|x| String::from(x.get_name()))
+        .filter(|x| x != "help")
+        .collect();
+
+    for name in names {
+        let sub = cli.find_subcommand_mut(&name).expect("subcommand listed above");
+        let usage = sub.render_usage();
+
+        page.control("SS", [name.as_str()])
+            .text([roman(sub.get_about().unwrap_or(""))])
+            .control("PP", [])
+            .text([bold(usage.trim_start_matches("USAGE:").trim())]);
+        roff_args(&mut page, sub.get_arguments().filter(|x| !x.is_global_set() && !x.is_hide_set() && x.get_id() != "help"));
+    }
+
+    page.render()
+}
@@ -0,0 +1,948 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use log::LevelFilter;
+use serde::de::{self, DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use cron::Schedule;
+
+use crate::env::apply_env;
+use crate::logging::parse_interval;
+
+/// Typed view of config.yaml. Every section is checked by Config::from_yaml so
+/// a missing or malformed key is reported before any command starts.
+/// Debug output redacts credentials, so the config can be logged.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    pub etherscan: Option<EtherscanConfig>,
+    pub rpc: Option<RpcConfig>,
+    pub twitter: Option<TwitterConfig>,
+    pub cache: Option<CacheConfig>,
+    pub ledger: Option<LedgerConfig>,
+    pub output: OutputConfig,
+    pub charts: ChartConfig,
+    pub logging: LoggingConfig,
+    pub server: ServerConfig,
+    pub publishers: BTreeMap<String, PublisherConfig>,
+    /// Status templates keyed by command, see TEMPLATE_COMMANDS
+    pub templates: BTreeMap<String, String>,
+    /// Jobs of the run command
+    pub schedule: Vec<JobConfig>,
+    pub pipelines: Vec<PipelineConfig>,
+    pub collections: Vec<Collection>,
+}
+
+/// Commands a job of the schedule section can run
+pub const JOB_COMMANDS: [&str; 3] = ["migration", "mint-activity", "export"];
+
+/// Commands whose status can be worded by a template, in the templates section or per collection
+pub const TEMPLATE_COMMANDS: [&str; 2] = ["migration", "mint-activity"];
+
+/// Dataset formats of output.format and export stages
+const DATA_FORMATS: [&str; 5] = ["csv", "json", "ndjson", "jsonl", "parquet"];
+
+/// A kind of pipeline stage, with the kinds of data it takes from its inputs and the kind it gives
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StageKind {
+    pub name: &'static str,
+    pub takes: &'static [&'static str],
+    pub gives: &'static str,
+}
+
+/// Stages a pipeline is built from. ingest takes no input, publish takes a status and any
+/// number of files to attach, every other stage takes exactly one input.
+pub const PIPELINE_STAGES: [StageKind; 8] = [
+    StageKind { name: "ingest", takes: &[], gives: "transfers" },
+    StageKind { name: "normalize", takes: &["transfers"], gives: "daily_mints" },
+    StageKind { name: "holders", takes: &["transfers"], gives: "holders" },
+    StageKind { name: "metrics", takes: &["daily_mints"], gives: "metrics" },
+    StageKind { name: "render", takes: &["metrics"], gives: "status" },
+    StageKind { name: "chart", takes: &["daily_mints"], gives: "file" },
+    StageKind { name: "export", takes: &["transfers", "daily_mints", "holders"], gives: "file" },
+    StageKind { name: "publish", takes: &["status", "file"], gives: "post" },
+];
+
+/// Utility fn to look up a stage kind by name
+pub fn stage_kind(name: &str) -> Option<&'static StageKind> {
+    PIPELINE_STAGES.iter().find(|x| x.name == name)
+}
+
+/// A credential. Debug prints whether it is set, never the value.
+#[derive(Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: &str) -> Secret {
+        Secret(String::from(value))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.is_empty() {
+            true => write!(f, "\"\""),
+            false => write!(f, "\"<redacted>\""),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EtherscanConfig {
+    pub api_key: Secret,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub calls_per_sec: Option<u32>,
+}
+
+/// The node url usually embeds the provider api key, so it is a Secret too
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RpcConfig {
+    pub url: Secret,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TwitterConfig {
+    pub consumer_key: Secret,
+    pub consumer_secret: Secret,
+    pub access_key: Secret,
+    pub access_secret: Secret,
+    /// How long to wait for twitter to process an attached image before giving up
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub media_timeout_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    pub path: String,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub finality_depth: Option<u64>,
+}
+
+/// HTTP api of the serve command, bind is the ip:port it listens on. The api answers from
+/// the cache section, synced for every collection each refresh_mins (10 when unset).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    #[serde(default = "default_server_bind")]
+    pub bind: String,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub refresh_mins: Option<u64>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig { bind: default_server_bind(), refresh_mins: None }
+    }
+}
+
+fn default_server_bind() -> String {
+    String::from("127.0.0.1:8080")
+}
+
+/// SQLite record of every published post. A post is skipped when its metrics did not change
+/// since the last one to the same publisher, or when that one is under min_interval_mins old.
+/// intervals_mins overrides the interval per publisher name.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LedgerConfig {
+    pub path: String,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub min_interval_mins: Option<u64>,
+    #[serde(default)]
+    pub intervals_mins: BTreeMap<String, u64>,
+}
+
+/// Where exported datasets go when --output is not given. --dry-run posts are
+/// written to outbox, <dir>/outbox when it is not set.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    #[serde(default = "default_output_dir")]
+    pub dir: String,
+    #[serde(default = "default_output_format")]
+    pub format: String,
+    #[serde(default)]
+    pub outbox: Option<String>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> OutputConfig {
+        OutputConfig { dir: default_output_dir(), format: default_output_format(), outbox: None }
+    }
+}
+
+/// Chart image settings, charts are written to the output dir when no path is given.
+/// format is html, png, svg or jpeg; html is width x height, images are rendered by kaleido
+/// at width x height times scale.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChartConfig {
+    #[serde(default = "default_chart_format")]
+    pub format: String,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub width: Option<usize>,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub height: Option<usize>,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub scale: Option<f64>,
+}
+
+impl Default for ChartConfig {
+    fn default() -> ChartConfig {
+        ChartConfig { format: default_chart_format(), width: None, height: None, scale: None }
+    }
+}
+
+/// Log file and stderr setup. level is the stderr threshold, file_level the log file one;
+/// modules maps a module path such as "hyper" to its own level.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    #[serde(default = "default_log_file")]
+    pub file: String,
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default = "default_file_level")]
+    pub file_level: String,
+    /// "pattern" for the classic one line layout or "json" for one json object per line
+    #[serde(default = "default_log_format")]
+    pub format: String,
+    #[serde(default)]
+    pub modules: BTreeMap<String, String>,
+    #[serde(default)]
+    pub rotation: Option<RotationConfig>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> LoggingConfig {
+        LoggingConfig {
+            file: default_log_file(),
+            level: default_log_level(),
+            file_level: default_file_level(),
+            format: default_log_format(),
+            modules: BTreeMap::new(),
+            rotation: None,
+        }
+    }
+}
+
+/// Rolls the log file once it is max_size_mb large or max_age old ("1 day", "12 hours"),
+/// keeping the last `keep` files as <file>.0, <file>.1, ...
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotationConfig {
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub max_size_mb: Option<u64>,
+    #[serde(default)]
+    pub max_age: Option<String>,
+    #[serde(default = "default_log_keep")]
+    pub keep: u32,
+}
+
+/// A channel statuses are published to, from the publishers section of the config,
+/// keyed by the name collections refer to it by. twitter uses the twitter section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum PublisherConfig {
+    Twitter,
+    /// Discord channel webhook, https://discord.com/api/webhooks/<id>/<token>
+    Discord {
+        webhook_url: Secret,
+        #[serde(default)]
+        username: Option<String>,
+    },
+    /// Telegram bot posting to a chat or channel, api_url defaults to https://api.telegram.org
+    Telegram {
+        bot_token: Secret,
+        chat_id: String,
+        #[serde(default)]
+        api_url: Option<String>,
+    },
+    /// Any endpoint taking the post as a json body
+    Webhook {
+        url: Secret,
+        #[serde(default)]
+        headers: BTreeMap<String, Secret>,
+    },
+}
+
+/// A job of the run command, from the schedule section of the config. cron is a five field
+/// crontab expression in UTC, "0 18 * * *" every day at 18:00 or "0 9 * * 1" every monday at
+/// 9:00 (weekdays 0 and 7 are sunday, names like MON work too), or one of @hourly, @daily, @weekly.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobConfig {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub cron: String,
+    pub command: String,
+    pub collection: String,
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub lookback_days: Option<u64>,
+    /// Publish like --post, to the publishers of the collection
+    #[serde(default)]
+    pub post: bool,
+    /// Write the post to the outbox like --dry-run
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl JobConfig {
+    /// Name the job is logged under, "<collection> <command>" unless set
+    pub fn name(&self) -> String {
+        match &self.name {
+            Some(x) => x.clone(),
+            None => format!("{} {}", self.collection, self.command),
+        }
+    }
+
+    /// Parses cron, five field expressions run at second 0 with crontab weekday numbers
+    pub fn schedule(&self) -> Result<Schedule, cron::error::Error> {
+        let cron = self.cron.trim();
+        let fields: Vec<&str> = cron.split_whitespace().collect();
+
+        match fields.as_slice() {
+            [minute, hour, day, month, weekday] if !cron.starts_with('@') => {
+                Schedule::from_str(&format!("0 {} {} {} {} {}", minute, hour, day, month, crontab_weekdays(weekday)))
+            },
+            _ => Schedule::from_str(cron),
+        }
+    }
+}
+
+/// Utility fn to rewrite a crontab weekday field (0 and 7 = sunday, 1 = monday .. 6 = saturday)
+/// for the cron crate, which counts 1 = sunday .. 7 = saturday. Numbers, ranges and steps are
+/// expanded to a list; names, * and anything else are left for the cron crate to parse.
+fn crontab_weekdays(field: &str) -> String {
+    let mut out: Vec<String> = vec![];
+
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<usize>().ok().filter(|x| *x > 0)),
+            None => (item, Some(1)),
+        };
+        let bounds = match range.split_once('-') {
+            _ if range == "*" && item != "*" => Some((0, 6)),
+            Some((first, last)) => first.parse::<u32>().ok().zip(last.parse::<u32>().ok()),
+            None if item.contains('/') => range.parse::<u32>().ok().map(|first| (first, 6)),
+            None => range.parse::<u32>().ok().map(|first| (first, first)),
+        };
+
+        match (bounds, step) {
+            (Some((first, last)), Some(step)) if first <= last && last <= 7 => {
+                for day in (first..=last).step_by(step).map(|x| (x % 7 + 1).to_string()) {
+                    if !out.contains(&day) { out.push(day); }
+                }
+            },
+            _ => out.push(String::from(item)),
+        }
+    }
+
+    out.join(",")
+}
+
+/// A pipeline of the pipelines section, run by the pipeline command against one collection.
+/// Its stages are wired into a DAG by their inputs, see PIPELINE_STAGES.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    pub name: String,
+    pub collection: String,
+    pub stages: Vec<StageConfig>,
+}
+
+impl PipelineConfig {
+    /// Stages in an order where each comes after its inputs, or the ids of the stages
+    /// left over when their inputs form a cycle. Unknown input ids are ignored.
+    pub fn order(&self) -> Result<Vec<&StageConfig>, Vec<String>> {
+        let known = |id: &String| self.stages.iter().any(|x| &x.id == id);
+        let mut ordered: Vec<&StageConfig> = vec![];
+        let mut pending: Vec<&StageConfig> = self.stages.iter().collect();
+
+        while !pending.is_empty() {
+            let ready = |stage: &&StageConfig| stage.inputs.iter()
+                .filter(|x| known(x))
+                .all(|x| ordered.iter().any(|done| &done.id == x));
+
+            let (next, rest): (Vec<&StageConfig>, Vec<&StageConfig>) = pending.into_iter().partition(ready);
+            if next.is_empty() {
+                return Err(rest.iter().map(|x| x.id.clone()).collect());
+            }
+            ordered.extend(next);
+            pending = rest;
+        }
+
+        Ok(ordered)
+    }
+
+    pub fn stage(&self, id: &str) -> Option<&StageConfig> {
+        self.stages.iter().find(|x| x.id == id)
+    }
+}
+
+/// A stage of a pipeline. stage names its kind in PIPELINE_STAGES, inputs the ids of
+/// the stages whose data it takes. The options apply to the kinds their docs name.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StageConfig {
+    pub id: String,
+    pub stage: String,
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// normalize: days of mints kept, all of them when unset or 0
+    #[serde(default, deserialize_with = "number_or_string")]
+    pub lookback_days: Option<u64>,
+    /// metrics: variables of the migration or mint-activity (default) status
+    #[serde(default)]
+    pub command: Option<String>,
+    /// render: template text, the template of the metrics command when unset
+    #[serde(default)]
+    pub template: Option<String>,
+    /// export: csv, json or parquet, output.format when unset
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// An NFT project the bot reports on, from the collections section of the config
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Collection {
+    pub name: String,
+    pub display_name: String,
+    pub address: String,
+    #[serde(default = "default_chain")]
+    pub chain: String,
+    /// ABI exposing MAX_SUPPLY(), used when max_supply is "contract"
+    #[serde(default)]
+    pub abi_path: Option<String>,
+    pub max_supply: MaxSupply,
+    /// First block worth scanning, transfers before it are skipped
+    #[serde(default)]
+    pub deployment_block: u64,
+    /// Names from the publishers section --post sends to, twitter alone when empty
+    #[serde(default)]
+    pub publish: Vec<String>,
+    /// Status templates of this collection keyed by command, over the templates section
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+}
+
+/// Either a fixed supply or `contract` to call MAX_SUPPLY() on the collection
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MaxSupply {
+    Fixed(u64),
+    Read(ReadFrom),
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadFrom {
+    Contract,
+}
+
+/// Environment overrides are strings, so numeric keys accept "5" as well as 5
+fn number_or_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString<T> {
+        Number(T),
+        String(String),
+    }
+
+    match NumberOrString::<T>::deserialize(deserializer)? {
+        NumberOrString::Number(x) => Ok(Some(x)),
+        NumberOrString::String(x) => x.parse::<T>().map(Some).map_err(de::Error::custom),
+    }
+}
+
+fn default_chain() -> String {
+    String::from("ethereum")
+}
+
+fn default_output_dir() -> String {
+    String::from(".")
+}
+
+fn default_output_format() -> String {
+    String::from("csv")
+}
+
+fn default_chart_format() -> String {
+    String::from("png")
+}
+
+fn default_log_file() -> String {
+    String::from("/tmp/bkeeper.log")
+}
+
+fn default_log_level() -> String {
+    String::from("info")
+}
+
+fn default_file_level() -> String {
+    String::from("trace")
+}
+
+fn default_log_format() -> String {
+    String::from("pattern")
+}
+
+fn default_log_keep() -> u32 {
+    5
+}
+
+const SECTIONS: [&str; 14] = [
+    "etherscan", "rpc", "twitter", "cache", "ledger", "output", "charts", "logging", "server", "publishers", "templates",
+    "schedule", "pipelines", "collections",
+];
+
+/// Every problem found while loading a config file, one line per key
+#[derive(Debug)]
+pub struct ConfigError {
+    pub path: String,
+    pub issues: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is invalid:", self.path)?;
+        for issue in &self.issues {
+            write!(f, "\n  - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Reads and validates the config file at path. Values set through DSB_ environment
+    /// variables or the secret files they point at take precedence over the file.
+    pub fn load(path: &str) -> Result<Config, ConfigError> {
+        let report = |issue: String| ConfigError { path: String::from(path), issues: vec![issue] };
+
+        let mut file_data = String::new();
+        File::open(path)
+            .and_then(|mut x| x.read_to_string(&mut file_data))
+            .map_err(|e| report(format!("cannot be read: {}", e)))?;
+
+        let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        Config::parse(&file_data, base_dir, std::env::vars()).map_err(|mut e| {
+            e.path = String::from(path);
+            e
+        })
+    }
+
+    /// Parses and validates yaml, relative abi paths being taken from the working directory.
+    /// The process environment is not consulted.
+    pub fn from_yaml(yaml: &str) -> Result<Config, ConfigError> {
+        Config::parse(yaml, Path::new(""), std::iter::empty())
+    }
+
+    /// Parses and validates yaml with the given environment variables as overrides
+    pub fn from_yaml_with_env<I>(yaml: &str, vars: I) -> Result<Config, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        Config::parse(yaml, Path::new(""), vars)
+    }
+
+    /// Parses every section independently so one bad key does not hide the others.
+    /// Relative abi paths are resolved against base_dir, the directory of the config file.
+    fn parse<I>(yaml: &str, base_dir: &Path, vars: I) -> Result<Config, ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut issues: Vec<String> = vec![];
+
+        let mut root: Mapping = match serde_yaml::from_str::<Option<Mapping>>(yaml) {
+            Ok(x) => x.unwrap_or_default(),
+            Err(e) => return Err(ConfigError { path: String::new(), issues: vec![e.to_string()] }),
+        };
+
+        apply_env(&mut root, vars, &mut issues);
+
+        for (key, _) in root.iter() {
+            match key.as_str() {
+                Some(x) if SECTIONS.contains(&x) => {},
+                Some(x) => issues.push(format!("{}: unknown section, expected one of {}", x, SECTIONS.join(", "))),
+                None => issues.push(format!("{:?}: section names must be strings", key)),
+            }
+        }
+
+        let mut config = Config {
+            etherscan: section(&root, "etherscan", &mut issues),
+            rpc: section(&root, "rpc", &mut issues),
+            twitter: section(&root, "twitter", &mut issues),
+            cache: section(&root, "cache", &mut issues),
+            ledger: section(&root, "ledger", &mut issues),
+            output: section(&root, "output", &mut issues).unwrap_or_default(),
+            charts: section(&root, "charts", &mut issues).unwrap_or_default(),
+            logging: section(&root, "logging", &mut issues).unwrap_or_default(),
+            server: section(&root, "server", &mut issues).unwrap_or_default(),
+            publishers: publishers(&root, &mut issues),
+            templates: section(&root, "templates", &mut issues).unwrap_or_default(),
+            schedule: section(&root, "schedule", &mut issues).unwrap_or_default(),
+            pipelines: section(&root, "pipelines", &mut issues).unwrap_or_default(),
+            collections: section(&root, "collections", &mut issues).unwrap_or_default(),
+        };
+
+        for collection in config.collections.iter_mut() {
+            if let Some(path) = collection.abi_path.as_mut() {
+                *path = base_dir.join(&path).to_string_lossy().into_owned();
+            }
+        }
+
+        issues.extend(config.validate());
+
+        match issues.is_empty() {
+            true => Ok(config),
+            false => Err(ConfigError { path: String::new(), issues }),
+        }
+    }
+
+    /// Checks values serde cannot: urls, addresses, ranges and cross references
+    fn validate(&self) -> Vec<String> {
+        let mut issues: Vec<String> = vec![];
+
+        if let Some(etherscan) = &self.etherscan {
+            require("etherscan.api_key", etherscan.api_key.expose(), &mut issues);
+            if let Some(url) = &etherscan.base_url {
+                check_url("etherscan.base_url", url, &mut issues);
+            }
+            if etherscan.calls_per_sec == Some(0) {
+                issues.push(String::from("etherscan.calls_per_sec: must be at least 1"));
+            }
+        }
+
+        if let Some(rpc) = &self.rpc {
+            check_url("rpc.url", rpc.url.expose(), &mut issues);
+        }
+
+        if let Some(twitter) = &self.twitter {
+            require("twitter.consumer_key", twitter.consumer_key.expose(), &mut issues);
+            require("twitter.consumer_secret", twitter.consumer_secret.expose(), &mut issues);
+            require("twitter.access_key", twitter.access_key.expose(), &mut issues);
+            require("twitter.access_secret", twitter.access_secret.expose(), &mut issues);
+            if twitter.media_timeout_secs == Some(0) {
+                issues.push(String::from("twitter.media_timeout_secs: must be at least 1"));
+            }
+        }
+
+        if let Some(cache) = &self.cache {
+            require("cache.path", &cache.path, &mut issues);
+            if cache.finality_depth == Some(0) {
+                issues.push(String::from("cache.finality_depth: must be at least 1"));
+            }
+        }
+
+        if !DATA_FORMATS.contains(&self.output.format.to_lowercase().as_str()) {
+            issues.push(format!("output.format: {:?} is not csv, json or parquet", self.output.format));
+        }
+
+        if !["html", "png", "svg", "jpeg", "jpg"].contains(&self.charts.format.to_lowercase().as_str()) {
+            issues.push(format!("charts.format: {:?} is not html, png, svg or jpeg", self.charts.format));
+        }
+        if self.charts.width == Some(0) {
+            issues.push(String::from("charts.width: must be at least 1"));
+        }
+        if self.charts.height == Some(0) {
+            issues.push(String::from("charts.height: must be at least 1"));
+        }
+        if let Some(scale) = self.charts.scale.filter(|x| x.is_nan() || *x <= 0.0) {
+            issues.push(format!("charts.scale: {} must be above 0", scale));
+        }
+
+        let levels = self.logging.modules.iter()
+            .map(|(module, level)| (format!("logging.modules.{}", module), level))
+            .chain([
+                (String::from("logging.level"), &self.logging.level),
+                (String::from("logging.file_level"), &self.logging.file_level),
+            ]);
+        for (key, level) in levels {
+            if LevelFilter::from_str(level).is_err() {
+                issues.push(format!("{}: {:?} is not off, error, warn, info, debug or trace", key, level));
+            }
+        }
+
+        if !["pattern", "json"].contains(&self.logging.format.as_str()) {
+            issues.push(format!("logging.format: {:?} is not pattern or json", self.logging.format));
+        }
+
+        if let Some(rotation) = &self.logging.rotation {
+            if rotation.max_size_mb.is_none() && rotation.max_age.is_none() {
+                issues.push(String::from("logging.rotation: needs max_size_mb or max_age"));
+            }
+            if rotation.max_size_mb == Some(0) {
+                issues.push(String::from("logging.rotation.max_size_mb: must be at least 1"));
+            }
+            if let Some(max_age) = rotation.max_age.as_ref().filter(|x| parse_interval(x).is_none()) {
+                issues.push(format!("logging.rotation.max_age: {:?} is not like \"1 day\" or \"12 hours\"", max_age));
+            }
+            if rotation.keep == 0 {
+                issues.push(String::from("logging.rotation.keep: must be at least 1"));
+            }
+        }
+
+        if self.server.bind.parse::<std::net::SocketAddr>().is_err() {
+            issues.push(format!("server.bind: {:?} is not an ip:port address", self.server.bind));
+        }
+        if self.server.refresh_mins == Some(0) {
+            issues.push(String::from("server.refresh_mins: must be at least 1"));
+        }
+
+        for (name, publisher) in &self.publishers {
+            let key = |field: &str| format!("publishers.{}.{}", name, field);
+
+            match publisher {
+                PublisherConfig::Twitter if self.twitter.is_none() => {
+                    issues.push(format!("publishers.{}: needs the twitter section", name));
+                },
+                PublisherConfig::Twitter => {},
+                PublisherConfig::Discord { webhook_url, .. } => check_url(&key("webhook_url"), webhook_url.expose(), &mut issues),
+                PublisherConfig::Telegram { bot_token, chat_id, api_url } => {
+                    require(&key("bot_token"), bot_token.expose(), &mut issues);
+                    require(&key("chat_id"), chat_id, &mut issues);
+                    if let Some(url) = api_url {
+                        check_url(&key("api_url"), url, &mut issues);
+                    }
+                },
+                PublisherConfig::Webhook { url, .. } => check_url(&key("url"), url.expose(), &mut issues),
+            }
+        }
+
+        if let Some(ledger) = &self.ledger {
+            require("ledger.path", &ledger.path, &mut issues);
+
+            // twitter is the publisher --post falls back to, outbox the one of --dry-run
+            let unknown = ledger.intervals_mins.keys()
+                .filter(|x| !self.publishers.contains_key(*x) && !["twitter", "outbox"].contains(&x.as_str()));
+            for name in unknown {
+                issues.push(format!("ledger.intervals_mins.{}: is not in the publishers section", name));
+            }
+        }
+
+        check_templates("templates", &self.templates, &mut issues);
+
+        let mut job_names = vec![];
+        for (i, job) in self.schedule.iter().enumerate() {
+            let key = |field: &str| format!("schedule[{}].{}", i, field);
+
+            if let Err(e) = job.schedule() {
+                issues.push(format!("{}: {:?} is not a cron expression, {}", key("cron"), job.cron, e));
+            }
+            if !JOB_COMMANDS.contains(&job.command.as_str()) {
+                issues.push(format!("{}: {:?} is not one of {}", key("command"), job.command, JOB_COMMANDS.join(", ")));
+            }
+            if !self.collections.iter().any(|x| x.name.eq_ignore_ascii_case(&job.collection)) {
+                issues.push(format!("{}: {:?} is not in the collections section", key("collection"), job.collection));
+            }
+            if job.lookback_days.is_some_and(|x| x > 180) {
+                issues.push(format!("{}: must be 180 or less", key("lookback_days")));
+            }
+            if job.post && job.dry_run {
+                issues.push(format!("{}: post and dry_run are exclusive", key("post")));
+            }
+            if job.command == "export" && (job.post || job.dry_run) {
+                issues.push(format!("{}: export does not post", key("post")));
+            }
+            if job_names.contains(&job.name()) {
+                issues.push(format!("{}: {:?} is used by an earlier job", key("name"), job.name()));
+            }
+            job_names.push(job.name());
+        }
+
+        for (i, pipeline) in self.pipelines.iter().enumerate() {
+            let key = |field: &str| format!("pipelines[{}].{}", i, field);
+
+            require(&key("name"), &pipeline.name, &mut issues);
+            if self.pipelines[..i].iter().any(|x| x.name == pipeline.name) {
+                issues.push(format!("{}: {:?} is used by an earlier pipeline", key("name"), pipeline.name));
+            }
+            if !self.collections.iter().any(|x| x.name.eq_ignore_ascii_case(&pipeline.collection)) {
+                issues.push(format!("{}: {:?} is not in the collections section", key("collection"), pipeline.collection));
+            }
+            if pipeline.stages.is_empty() {
+                issues.push(format!("{}: needs at least one stage", key("stages")));
+            }
+
+            for (j, stage) in pipeline.stages.iter().enumerate() {
+                let key = |field: &str| format!("pipelines[{}].stages[{}].{}", i, j, field);
+                check_stage(pipeline, j, stage, &key, &mut issues);
+            }
+
+            if let Err(ids) = pipeline.order() {
+                issues.push(format!("{}: {} take each other as inputs", key("stages"), ids.join(", ")));
+            }
+        }
+
+        for (i, collection) in self.collections.iter().enumerate() {
+            let key = |field: &str| format!("collections[{}].{}", i, field);
+
+            require(&key("name"), &collection.name, &mut issues);
+            if self.collections[..i].iter().any(|x| x.name.eq_ignore_ascii_case(&collection.name)) {
+                issues.push(format!("{}: {:?} is listed twice", key("name"), collection.name));
+            }
+
+            if !is_address(&collection.address) {
+                issues.push(format!("{}: {:?} is not a 0x prefixed 20 byte address", key("address"), collection.address));
+            }
+
+            match collection.max_supply {
+                MaxSupply::Fixed(0) => issues.push(format!("{}: must be at least 1", key("max_supply"))),
+                MaxSupply::Read(_) if self.rpc.is_none() => {
+                    issues.push(format!("{}: reading from the contract needs the rpc section", key("max_supply")));
+                },
+                _ => {},
+            }
+
+            if let Some(path) = collection.abi_path.as_ref().filter(|x| !Path::new(x).is_file()) {
+                issues.push(format!("{}: {} does not exist", key("abi_path"), path));
+            }
+
+            for name in collection.publish.iter().filter(|x| !self.publishers.contains_key(*x)) {
+                issues.push(format!("{}: {:?} is not in the publishers section", key("publish"), name));
+            }
+
+            check_templates(&key("templates"), &collection.templates, &mut issues);
+        }
+
+        issues
+    }
+}
+
+/// Utility fn to check for a 0x prefixed 20 byte hex address
+pub(crate) fn is_address(value: &str) -> bool {
+    let hex = value.strip_prefix("0x").unwrap_or("");
+    hex.len() == 40 && hex.chars().all(|x| x.is_ascii_hexdigit())
+}
+
+/// Utility fn to deserialize one top level section, recording the error if it is malformed
+fn section<T: DeserializeOwned>(root: &Mapping, name: &str, issues: &mut Vec<String>) -> Option<T> {
+    let value = root.get(&Value::from(name))?;
+
+    match serde_yaml::from_value(value.clone()) {
+        Ok(x) => Some(x),
+        Err(e) => {
+            issues.push(format!("{}: {}", name, e));
+            None
+        },
+    }
+}
+
+/// Utility fn to deserialize the publishers section entry by entry, so a malformed
+/// publisher is reported under its own name and the others are still checked
+fn publishers(root: &Mapping, issues: &mut Vec<String>) -> BTreeMap<String, PublisherConfig> {
+    let entries: BTreeMap<String, Value> = section(root, "publishers", issues).unwrap_or_default();
+
+    entries.into_iter()
+        .filter_map(|(name, value)| match serde_yaml::from_value(value) {
+            Ok(x) => Some((name, x)),
+            Err(e) => {
+                issues.push(format!("publishers.{}: {}", name, e));
+                None
+            },
+        })
+        .collect()
+}
+
+fn require(key: &str, value: &str, issues: &mut Vec<String>) {
+    if value.trim().is_empty() {
+        issues.push(format!("{}: is required", key));
+    }
+}
+
+fn check_templates(key: &str, templates: &BTreeMap<String, String>, issues: &mut Vec<String>) {
+    for command in templates.keys().filter(|x| !TEMPLATE_COMMANDS.contains(&x.as_str())) {
+        issues.push(format!("{}.{}: no such command, expected one of {}", key, command, TEMPLATE_COMMANDS.join(", ")));
+    }
+}
+
+/// Checks the kind, inputs and options of stage j of a pipeline
+fn check_stage(pipeline: &PipelineConfig, j: usize, stage: &StageConfig, key: &dyn Fn(&str) -> String, issues: &mut Vec<String>) {
+    require(&key("id"), &stage.id, issues);
+    if pipeline.stages[..j].iter().any(|x| x.id == stage.id) {
+        issues.push(format!("{}: {:?} is used by an earlier stage", key("id"), stage.id));
+    }
+
+    let kind = match stage_kind(&stage.stage) {
+        Some(x) => x,
+        None => {
+            let names: Vec<&str> = PIPELINE_STAGES.iter().map(|x| x.name).collect();
+            issues.push(format!("{}: {:?} is not one of {}", key("stage"), stage.stage, names.join(", ")));
+            return;
+        },
+    };
+
+    let mut gives = vec![];
+    for id in &stage.inputs {
+        match pipeline.stage(id).filter(|x| x.id != stage.id) {
+            Some(input) => gives.extend(stage_kind(&input.stage).map(|x| x.gives)),
+            None => issues.push(format!("{}: {:?} is not another stage of the pipeline", key("inputs"), id)),
+        }
+    }
+
+    // stages of an unknown kind are reported on their own
+    if gives.len() == stage.inputs.len() {
+        let fits = match kind.name {
+            "ingest" => gives.is_empty(),
+            "publish" => gives.iter().filter(|x| **x == "status").count() == 1 && gives.iter().all(|x| kind.takes.contains(x)),
+            _ => gives.len() == 1 && kind.takes.contains(&gives[0]),
+        };
+        if !fits {
+            let takes = match kind.name {
+                "ingest" => String::from("no input"),
+                "publish" => String::from("one status and any files"),
+                _ => format!("one input of {}", kind.takes.join(" or ")),
+            };
+            issues.push(format!("{}: {} takes {}, got [{}]", key("inputs"), kind.name, takes, gives.join(", ")));
+        }
+    }
+
+    let mut option = |field: &str, set: bool, kinds: &[&str]| {
+        if set && !kinds.contains(&kind.name) {
+            issues.push(format!("{}: only applies to {}", key(field), kinds.join(", ")));
+        }
+    };
+    option("lookback_days", stage.lookback_days.is_some(), &["normalize"]);
+    option("command", stage.command.is_some(), &["metrics"]);
+    option("template", stage.template.is_some(), &["render"]);
+    option("format", stage.format.is_some(), &["export"]);
+
+    if stage.lookback_days.is_some_and(|x| x > 180) {
+        issues.push(format!("{}: must be 180 or less", key("lookback_days")));
+    }
+    if let Some(command) = stage.command.as_ref().filter(|x| !TEMPLATE_COMMANDS.contains(&x.as_str())) {
+        issues.push(format!("{}: {:?} is not one of {}", key("command"), command, TEMPLATE_COMMANDS.join(", ")));
+    }
+    if let Some(format) = stage.format.as_ref().filter(|x| !DATA_FORMATS.contains(&x.to_lowercase().as_str())) {
+        issues.push(format!("{}: {:?} is not csv, json or parquet", key("format"), format));
+    }
+}
+
+fn check_url(key: &str, value: &str, issues: &mut Vec<String>) {
+    if !(value.starts_with("http://") || value.starts_with("https://")) {
+        issues.push(format!("{}: is not an http(s) url", key));
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde_yaml::{Mapping, Value};
+
+/// Prefix of environment overrides, DSB_<SECTION>_<KEY> e.g. DSB_ETHERSCAN_API_KEY.
+/// DSB_<SECTION>_<KEY>_FILE names a file holding the value instead, e.g. a docker secret.
+pub const ENV_PREFIX: &str = "DSB_";
+
+/// Short names of the flat config layout, still accepted for the credentials
+const ENV_ALIASES: [(&str, &str, &str); 8] = [
+    ("ES_KEY", "etherscan", "api_key"),
+    ("ES_BASE_URL", "etherscan", "base_url"),
+    ("ES_CALLS_PER_SEC", "etherscan", "calls_per_sec"),
+    ("ALCHEMY_URL", "rpc", "url"),
+    ("CON_KEY", "twitter", "consumer_key"),
+    ("CON_SECRET", "twitter", "consumer_secret"),
+    ("ACC_KEY", "twitter", "access_key"),
+    ("ACC_SECRET", "twitter", "access_secret"),
+];
+
+/// Sections that can be set from the environment, collections being a list cannot
+const ENV_SECTIONS: [&str; 9] = ["etherscan", "rpc", "twitter", "cache", "ledger", "output", "charts", "logging", "server"];
+
+/// Where an override came from, later variants win:
+/// config.yaml < alias file < full name file < alias value < full name value
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Origin {
+    AliasFile,
+    File,
+    AliasValue,
+    Value,
+}
+
+/// Utility fn to map a variable name without prefix to (section, key, is_alias)
+fn resolve(var: &str) -> Option<(&'static str, String, bool)> {
+    if let Some((_, section, key)) = ENV_ALIASES.iter().find(|(alias, _, _)| *alias == var) {
+        return Some((section, String::from(*key), true));
+    }
+
+    let (section, key) = var.split_once('_')?;
+    let section = ENV_SECTIONS.iter().find(|x| x.eq_ignore_ascii_case(section))?;
+
+    match key.is_empty() {
+        true => None,
+        false => Some((section, key.to_lowercase(), false)),
+    }
+}
+
+/// Overwrites config values with DSB_ environment variables, creating sections as needed.
+/// Variables that do not map to a section, or whose secret file cannot be read, are reported.
+pub(crate) fn apply_env<I>(root: &mut Mapping, vars: I, issues: &mut Vec<String>)
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut overrides: BTreeMap<(&str, String), (Origin, String)> = BTreeMap::new();
+
+    for (name, value) in vars {
+        let var = match name.strip_prefix(ENV_PREFIX) {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let (target, is_file) = match var.strip_suffix("_FILE").and_then(resolve) {
+            Some(x) => (Some(x), true),
+            None => (resolve(var), false),
+        };
+
+        let (section, key, is_alias) = match target {
+            Some(x) => x,
+            None => {
+                issues.push(format!("{}: does not name a config key, expected {}<SECTION>_<KEY>", name, ENV_PREFIX));
+                continue;
+            },
+        };
+
+        let (origin, value) = match is_file {
+            true => match fs::read_to_string(&value) {
+                Ok(x) => (if is_alias { Origin::AliasFile } else { Origin::File }, String::from(x.trim_end())),
+                Err(e) => {
+                    issues.push(format!("{}: cannot read {}: {}", name, value, e));
+                    continue;
+                },
+            },
+            false => (if is_alias { Origin::AliasValue } else { Origin::Value }, value),
+        };
+
+        match overrides.get(&(section, key.clone())) {
+            Some((current, _)) if *current >= origin => {},
+            _ => { overrides.insert((section, key), (origin, value)); },
+        }
+    }
+
+    for ((section, key), (_, value)) in overrides {
+        let entry = root.entry(Value::from(section)).or_insert_with(|| Value::Mapping(Mapping::new()));
+
+        match entry {
+            Value::Mapping(x) => { x.insert(Value::from(key), Value::from(value)); },
+            _ => issues.push(format!("{}: is not a section, cannot apply {}{}_{}", section, ENV_PREFIX, section.to_uppercase(), key.to_uppercase())),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::str::FromStr;
+
+use log::LevelFilter;
+use log4rs::{
+    append::{console::{ConsoleAppender, Target},
+             file::FileAppender,
+             rolling_file::{
+                 policy::compound::{
+                     roll::fixed_window::FixedWindowRoller,
+                     trigger::{size::SizeTrigger, time::{TimeTrigger, TimeTriggerConfig, TimeTriggerInterval}, Trigger},
+                     CompoundPolicy,
+                 },
+                 LogFile, RollingFileAppender,
+             },
+             Append,
+    },
+    config::{Appender, Logger, Root},
+    encode::{json::JsonEncoder, pattern::PatternEncoder, Encode},
+    filter::threshold::ThresholdFilter,
+};
+
+use crate::{ConfigError, LoggingConfig};
+
+const LOG_PATTERN: &str = "{h({d(%m-%d-%Y %H:%M:%S)})}|{m}{n}";
+
+/// Stderr threshold chosen on the command line with --quiet / --verbose
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose(u64),
+}
+
+impl Verbosity {
+    pub fn from_args(cli_args: &clap::ArgMatches) -> Verbosity {
+        match (cli_args.is_present("quiet"), cli_args.occurrences_of("verbose")) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, x) => Verbosity::Verbose(x),
+        }
+    }
+
+    fn console_level(&self, configured: LevelFilter) -> LevelFilter {
+        match self {
+            Verbosity::Quiet => LevelFilter::Error,
+            Verbosity::Normal => configured,
+            Verbosity::Verbose(1) => configured.max(LevelFilter::Debug),
+            Verbosity::Verbose(_) => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Rolls the log once any of its triggers fires, so size and age limits can be combined
+#[derive(Debug)]
+struct AnyTrigger(Vec<Box<dyn Trigger>>);
+
+impl Trigger for AnyTrigger {
+    fn trigger(&self, file: &LogFile) -> anyhow::Result<bool> {
+        for trigger in &self.0 {
+            if trigger.trigger(file)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn is_pre_process(&self) -> bool {
+        self.0.iter().any(|x| x.is_pre_process())
+    }
+}
+
+/// Utility fn to parse a rotation age such as "1 day" or "12 hours"
+pub(crate) fn parse_interval(value: &str) -> Option<TimeTriggerInterval> {
+    let value = value.trim().to_lowercase();
+    let (count, unit) = value.split_once(' ').unwrap_or((value.as_str(), "day"));
+    let count = i64::from_str(count.trim()).ok().filter(|x| *x > 0)?;
+
+    match unit.trim().trim_end_matches('s') {
+        "minute" => Some(TimeTriggerInterval::Minute(count)),
+        "hour" => Some(TimeTriggerInterval::Hour(count)),
+        "day" => Some(TimeTriggerInterval::Day(count)),
+        "week" => Some(TimeTriggerInterval::Week(count)),
+        "month" => Some(TimeTriggerInterval::Month(count)),
+        _ => None,
+    }
+}
+
+/// Sets up the log file and stderr appenders from the logging config section
+pub fn init_logger(config: &LoggingConfig, verbosity: Verbosity) -> Result<(), ConfigError> {
+    let report = |issue: String| ConfigError { path: String::from("logging"), issues: vec![issue] };
+
+    // levels were checked by Config::from_yaml
+    let level = |x: &str| LevelFilter::from_str(x).unwrap_or(LevelFilter::Info);
+
+    let encoder = || -> Box<dyn Encode> {
+        match config.format.as_str() {
+            "json" => Box::new(JsonEncoder::new()),
+            _ => Box::new(PatternEncoder::new(LOG_PATTERN)),
+        }
+    };
+
+    let logfile: Box<dyn Append> = match &config.rotation {
+        None => Box::new(FileAppender::builder()
+            .encoder(encoder())
+            .build(&config.file)
+            .map_err(|e| report(format!("{}: {}", config.file, e)))?),
+        Some(rotation) => {
+            let mut triggers: Vec<Box<dyn Trigger>> = vec![];
+            if let Some(max_size_mb) = rotation.max_size_mb {
+                triggers.push(Box::new(SizeTrigger::new(max_size_mb * 1024 * 1024)));
+            }
+            if let Some(interval) = rotation.max_age.as_deref().and_then(parse_interval) {
+                triggers.push(Box::new(TimeTrigger::new(TimeTriggerConfig { interval, ..Default::default() })));
+            }
+
+            let roller = FixedWindowRoller::builder()
+                .build(&format!("{}.{{}}", config.file), rotation.keep)
+                .map_err(|e| report(format!("rotation: {}", e)))?;
+
+            Box::new(RollingFileAppender::builder()
+                .encoder(encoder())
+                .build(&config.file, Box::new(CompoundPolicy::new(Box::new(AnyTrigger(triggers)), Box::new(roller))))
+                .map_err(|e| report(format!("{}: {}", config.file, e)))?)
+        },
+    };
+
+    let stderr = ConsoleAppender::builder().target(Target::Stderr).build();
+    let console_level = verbosity.console_level(level(&config.level));
+
+    let mut builder = log4rs::Config::builder()
+        .appender(Appender::builder()
+                  .filter(Box::new(ThresholdFilter::new(level(&config.file_level))))
+                  .build("logfile", logfile))
+        .appender(Appender::builder()
+                  .filter(Box::new(ThresholdFilter::new(console_level)))
+                  .build("stderr", Box::new(stderr)));
+
+    for (module, module_level) in &config.modules {
+        builder = builder.logger(Logger::builder().build(module, level(module_level)));
+    }
+
+    let log_config = builder
+        .build(Root::builder()
+               .appender("logfile")
+               .appender("stderr")
+               .build(level(&config.file_level).max(console_level)),
+        )
+        .map_err(|e| report(e.to_string()))?;
+
+    log4rs::init_config(log_config).map_err(|e| report(e.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use conf::{build_cli, render_manpage, write_completions, Shell, Verbosity};
+
+#[test]
+fn subcommands_take_typed_args() {
+    let cli_args = build_cli()
+        .try_get_matches_from(["deluxe_stat_bot", "mint-activity", "-n", "bees", "-l", "30", "-vv"])
+        .unwrap();
+
+    assert_eq!(Verbosity::from_args(&cli_args), Verbosity::Verbose(2));
+    assert_eq!(cli_args.value_of("conf"), Some("config.yaml"));
+
+    let (name, args) = cli_args.subcommand().unwrap();
+    assert_eq!(name, "mint-activity");
+    assert_eq!(args.value_of("collection"), Some("bees"));
+    assert_eq!(args.get_one::<u64>("lookback"), Some(&30));
+}
+
+#[test]
+fn invalid_args_are_rejected() {
+    let invalid = [
+        vec!["deluxe_stat_bot"],
+        vec!["deluxe_stat_bot", "mint-activity", "-n", "bees", "-l", "181"],
+        vec!["deluxe_stat_bot", "holders"],
+        vec!["deluxe_stat_bot", "holders", "-n", "bees", "-a", "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"],
+        vec!["deluxe_stat_bot", "export", "-a", "0x4BB3"],
+        vec!["deluxe_stat_bot", "export", "-n", "bees", "-f", "xlsx"],
+        vec!["deluxe_stat_bot", "render-template", "holders", "-n", "bees"],
+        vec!["deluxe_stat_bot", "render-template", "migration", "-n", "bees", "-t", "{{total}}", "--template-file", "status.hbs"],
+    ];
+
+    for args in invalid {
+        assert!(build_cli().try_get_matches_from(&args).is_err(), "{:?} was accepted", args);
+    }
+}
+
+#[test]
+fn completions_and_manpage_cover_every_subcommand() {
+    let manpage = render_manpage();
+
+    for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+        let mut script = vec![];
+        write_completions(shell, &mut script);
+        let script = String::from_utf8(script).unwrap();
+
+        for name in ["mint-activity", "migration", "holders", "export", "render-template", "history", "pipeline", "serve", "run", "completions", "manpage"] {
+            assert!(script.contains(name), "{:?} completions miss {}", shell, name);
+            assert!(manpage.contains(&format!(".SS {}", name)), "man page misses {}", name);
+        }
+    }
+
+    assert!(manpage.starts_with(".ie") && manpage.contains(".TH DELUXE_STAT_BOT 1"));
+    assert!(manpage.contains("\\fB\\-\\-lookback\\-days\\fR"));
+}
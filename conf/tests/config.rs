@@ -0,0 +1,352 @@
+use std::io::Write;
+
+use conf::{Config, MaxSupply};
+
+const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../config.yaml");
+
+const VALID: &str = r#"
+etherscan:
+  api_key: "KEY"
+  calls_per_sec: 3
+cache:
+  path: "/tmp/transfers.db"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+"#;
+
+#[test]
+fn valid_config_is_typed() {
+    let config = Config::from_yaml(VALID).unwrap();
+
+    let etherscan = config.etherscan.unwrap();
+    assert_eq!(etherscan.api_key.expose(), "KEY");
+    assert_eq!(etherscan.calls_per_sec, Some(3));
+    assert_eq!(config.cache.unwrap().finality_depth, None);
+    assert_eq!(config.collections[0].max_supply, MaxSupply::Fixed(6900));
+    assert_eq!(config.logging.level, "info");
+    assert!(config.twitter.is_none());
+}
+
+#[test]
+fn every_problem_is_reported() {
+    let yaml = r#"
+etherscan:
+  api_key: ""
+  calls_per_sec: "fast"
+twiter:
+  consumer_key: "x"
+charts:
+  format: "gif"
+  scale: 0
+logging:
+  level: "loud"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB3"
+    max_supply: "contract"
+"#;
+
+    let err = Config::from_yaml(yaml).unwrap_err();
+    let report = err.to_string();
+
+    assert_eq!(err.issues.len(), 7, "{}", report);
+    assert!(report.contains("twiter: unknown section"));
+    assert!(report.contains("charts.format"));
+    assert!(report.contains("charts.scale"));
+    assert!(report.contains("etherscan:"));
+    assert!(report.contains("logging.level"));
+    assert!(report.contains("collections[0].address"));
+    assert!(report.contains("collections[0].max_supply: reading from the contract needs the rpc section"));
+}
+
+#[test]
+fn typo_in_a_key_is_reported() {
+    let err = Config::from_yaml("etherscan:\n  api_kye: \"KEY\"\n").unwrap_err();
+
+    assert!(err.to_string().contains("unknown field `api_kye`"), "{}", err);
+}
+
+#[test]
+fn sample_config_only_lacks_credentials() {
+    let err = Config::load(CONFIG).unwrap_err();
+
+    let keys: Vec<&str> = err.issues.iter().map(|x| x.split(':').next().unwrap()).collect();
+    assert_eq!(keys, vec![
+        "etherscan.api_key",
+        "twitter.consumer_key",
+        "twitter.consumer_secret",
+        "twitter.access_key",
+        "twitter.access_secret",
+    ]);
+}
+
+fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter().map(|(k, v)| (String::from(*k), String::from(*v))).collect()
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let vars = env(&[
+        ("DSB_ETHERSCAN_API_KEY", "FROM_ENV"),
+        ("DSB_ETHERSCAN_CALLS_PER_SEC", "2"),
+        ("DSB_ALCHEMY_URL", "https://node.example/v2/abc"),
+        ("HOME", "/root"),
+    ]);
+
+    let config = Config::from_yaml_with_env(VALID, vars).unwrap();
+
+    let etherscan = config.etherscan.unwrap();
+    assert_eq!(etherscan.api_key.expose(), "FROM_ENV");
+    assert_eq!(etherscan.calls_per_sec, Some(2));
+    assert_eq!(config.rpc.unwrap().url.expose(), "https://node.example/v2/abc");
+}
+
+#[test]
+fn secret_files_rank_below_values() {
+    let mut secret = tempfile::NamedTempFile::new().unwrap();
+    writeln!(secret, "FROM_FILE").unwrap();
+    let path = secret.path().to_str().unwrap();
+
+    let from_file = Config::from_yaml_with_env(VALID, env(&[("DSB_ES_KEY_FILE", path)])).unwrap();
+    assert_eq!(from_file.etherscan.unwrap().api_key.expose(), "FROM_FILE");
+
+    let vars = env(&[("DSB_ETHERSCAN_API_KEY_FILE", path), ("DSB_ES_KEY", "ALIAS"), ("DSB_ETHERSCAN_API_KEY", "FULL")]);
+    let config = Config::from_yaml_with_env(VALID, vars).unwrap();
+    assert_eq!(config.etherscan.unwrap().api_key.expose(), "FULL");
+
+    let vars = env(&[("DSB_ETHERSCAN_API_KEY_FILE", path), ("DSB_ES_KEY", "ALIAS")]);
+    let config = Config::from_yaml_with_env(VALID, vars).unwrap();
+    assert_eq!(config.etherscan.unwrap().api_key.expose(), "ALIAS");
+}
+
+#[test]
+fn environment_problems_are_reported() {
+    let vars = env(&[
+        ("DSB_TWITTER_CONSUMER_KEY", "KEY"),
+        ("DSB_ETHERSCAN_API_KEY_FILE", "/nonexistent/es_key"),
+        ("DSB_WEBHOOK", "x"),
+    ]);
+
+    let err = Config::from_yaml_with_env(VALID, vars).unwrap_err();
+    let report = err.to_string();
+
+    assert!(report.contains("DSB_ETHERSCAN_API_KEY_FILE: cannot read /nonexistent/es_key"), "{}", report);
+    assert!(report.contains("DSB_WEBHOOK: does not name a config key"), "{}", report);
+    assert!(report.contains("twitter: missing field `consumer_secret`"), "{}", report);
+}
+
+#[test]
+fn debug_output_redacts_credentials() {
+    let vars = env(&[("DSB_ETHERSCAN_API_KEY", "s3cr3t"), ("DSB_RPC_URL", "https://node.example/v2/k3y")]);
+    let config = Config::from_yaml_with_env(VALID, vars).unwrap();
+
+    let logged = format!("{:?}", config);
+
+    assert!(!logged.contains("s3cr3t"), "{}", logged);
+    assert!(!logged.contains("k3y"), "{}", logged);
+    assert!(logged.contains("<redacted>"));
+}
+
+#[test]
+fn logging_section_is_checked() {
+    let yaml = r#"
+logging:
+  format: "xml"
+  modules:
+    hyper: "chatty"
+  rotation:
+    max_age: "fortnight"
+    keep: 0
+"#;
+
+    let err = Config::from_yaml(yaml).unwrap_err();
+    let report = err.to_string();
+
+    assert_eq!(err.issues.len(), 4, "{}", report);
+    assert!(report.contains("logging.format"));
+    assert!(report.contains("logging.modules.hyper"));
+    assert!(report.contains("logging.rotation.max_age"));
+    assert!(report.contains("logging.rotation.keep"));
+}
+
+#[test]
+fn publishers_section_is_checked() {
+    let yaml = r#"
+publishers:
+  tweets:
+    type: twitter
+  alerts:
+    type: discord
+    webhook_url: "discord.com/api/webhooks/1/abc"
+  news:
+    type: telegram
+    bot_token: "123:abc"
+    chat_id: "@bears"
+    parse_mode: "html"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+    publish: ["tweets", "alerts", "mastodon"]
+"#;
+
+    let err = Config::from_yaml(yaml).unwrap_err();
+    let report = err.to_string();
+
+    assert_eq!(err.issues.len(), 4, "{}", report);
+    assert!(report.contains("publishers.news: unknown field `parse_mode`"), "{}", report);
+    assert!(report.contains("publishers.tweets: needs the twitter section"), "{}", report);
+    assert!(report.contains("publishers.alerts.webhook_url"), "{}", report);
+    assert!(report.contains("collections[0].publish: \"mastodon\""), "{}", report);
+}
+
+#[test]
+fn templates_are_keyed_by_command() {
+    let yaml = r#"
+templates:
+  migration: "{{display_name}} is {{fixed progress 2}}% migrated"
+  mint_activity: "{{total}} minted"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+    templates:
+      holders: "{{rank}}"
+"#;
+
+    let err = Config::from_yaml(yaml).unwrap_err();
+    let report = err.to_string();
+
+    assert_eq!(err.issues.len(), 2, "{}", report);
+    assert!(report.contains("templates.mint_activity: no such command"), "{}", report);
+    assert!(report.contains("collections[0].templates.holders: no such command"), "{}", report);
+}
+
+#[test]
+fn ledger_intervals_name_publishers() {
+    let yaml = r#"
+ledger:
+  path: "/tmp/posts.db"
+  min_interval_mins: "60"
+  intervals_mins:
+    twitter: 240
+    alerts: 10
+"#;
+
+    let err = Config::from_yaml(yaml).unwrap_err();
+
+    assert_eq!(err.issues, vec![String::from("ledger.intervals_mins.alerts: is not in the publishers section")]);
+}
+
+#[test]
+fn schedule_jobs_are_checked() {
+    let yaml = r#"
+schedule:
+  - cron: "0 18 * * *"
+    command: "migration"
+    collection: "bears"
+    post: true
+  - cron: "0 25 * * *"
+    command: "holders"
+    collection: "bees"
+  - name: "weekly"
+    cron: "@weekly"
+    command: "mint-activity"
+    collection: "bears"
+    post: true
+    dry_run: true
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+"#;
+
+    let err = Config::from_yaml(yaml).unwrap_err();
+    let report = err.to_string();
+
+    assert_eq!(err.issues.len(), 4, "{}", report);
+    assert!(report.contains("schedule[1].cron: \"0 25 * * *\" is not a cron expression"), "{}", report);
+    assert!(report.contains("schedule[1].command: \"holders\" is not one of migration, mint-activity, export"), "{}", report);
+    assert!(report.contains("schedule[1].collection: \"bees\" is not in the collections section"), "{}", report);
+    assert!(report.contains("schedule[2].post: post and dry_run are exclusive"), "{}", report);
+}
+
+#[test]
+fn pipelines_are_checked_as_a_dag() {
+    let yaml = r#"
+pipelines:
+  - name: "daily"
+    collection: "bears"
+    stages:
+      - id: "transfers"
+        stage: "ingest"
+        lookback_days: 7
+      - id: "mints"
+        stage: "normalize"
+        inputs: ["transfers"]
+        lookback_days: 7
+      - id: "status"
+        stage: "render"
+        inputs: ["mints"]
+      - id: "post"
+        stage: "publish"
+        inputs: ["chart"]
+      - id: "csv"
+        stage: "export"
+        inputs: ["mints"]
+        format: "xlsx"
+      - id: "tweet"
+        stage: "tweet"
+  - name: "loop"
+    collection: "bees"
+    stages:
+      - id: "a"
+        stage: "normalize"
+        inputs: ["b"]
+      - id: "b"
+        stage: "normalize"
+        inputs: ["a"]
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+"#;
+
+    let err = Config::from_yaml(yaml).unwrap_err();
+    let report = err.to_string();
+
+    assert_eq!(err.issues.len(), 9, "{}", report);
+    assert!(report.contains("pipelines[0].stages[0].lookback_days: only applies to normalize"), "{}", report);
+    assert!(report.contains("pipelines[0].stages[2].inputs: render takes one input of metrics, got [daily_mints]"), "{}", report);
+    assert!(report.contains("pipelines[0].stages[3].inputs: \"chart\" is not another stage of the pipeline"), "{}", report);
+    assert!(report.contains("pipelines[0].stages[4].format: \"xlsx\" is not csv, json or parquet"), "{}", report);
+    assert!(report.contains("pipelines[0].stages[5].stage: \"tweet\" is not one of ingest, normalize"), "{}", report);
+    assert!(report.contains("pipelines[1].collection: \"bees\" is not in the collections section"), "{}", report);
+    assert!(report.contains("pipelines[1].stages[0].inputs: normalize takes one input of transfers, got [daily_mints]"), "{}", report);
+    assert!(report.contains("pipelines[1].stages: a, b take each other as inputs"), "{}", report);
+}
+
+#[test]
+fn server_bind_is_an_address() {
+    assert_eq!(Config::default().server.bind, "127.0.0.1:8080");
+
+    let err = Config::from_yaml("server:\n  bind: \"localhost\"\n").unwrap_err();
+    assert_eq!(err.issues, vec![String::from("server.bind: \"localhost\" is not an ip:port address")]);
+}
+
+#[test]
+fn server_refresh_is_in_minutes() {
+    assert_eq!(Config::default().server.refresh_mins, None);
+    assert_eq!(Config::from_yaml("server:\n  refresh_mins: \"5\"\n").unwrap().server.refresh_mins, Some(5));
+
+    let err = Config::from_yaml("server:\n  refresh_mins: 0\n").unwrap_err();
+    assert_eq!(err.issues, vec![String::from("server.refresh_mins: must be at least 1")]);
+}
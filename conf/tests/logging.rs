@@ -0,0 +1,34 @@
+use conf::{init_logger, Config, Verbosity};
+
+/// init_logger installs the global logger, so this file holds a single test
+#[test]
+fn json_log_file_with_module_levels() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("bot.log");
+
+    let yaml = format!(r#"
+logging:
+  file: "{}"
+  format: "json"
+  file_level: "info"
+  modules:
+    noisy: "error"
+  rotation:
+    max_size_mb: 1
+    max_age: "1 day"
+    keep: 2
+"#, file.display());
+    let config = Config::from_yaml(&yaml).unwrap();
+
+    init_logger(&config.logging, Verbosity::Quiet).unwrap();
+    log::info!(target: "bot", "main|starting");
+    log::debug!(target: "bot", "main|hidden");
+    log::warn!(target: "noisy", "noisy|hidden");
+
+    let logged = std::fs::read_to_string(&file).unwrap();
+    let lines: Vec<serde_json::Value> = logged.lines().map(|x| serde_json::from_str(x).unwrap()).collect();
+
+    assert_eq!(lines.len(), 1, "{}", logged);
+    assert_eq!(lines[0]["message"], "main|starting");
+    assert_eq!(lines[0]["level"], "INFO");
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use log::{info, warn};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use conf::CacheConfig;
+
+use crate::{Error, EtherscanClient, Result};
+
+/// Blocks with fewer confirmations are provisional and re-fetched on the next sync
+pub const FINALITY_DEPTH: u64 = 64;
+
+/// Persistent SQLite store of raw tokennfttx rows keyed by contract, with the
+/// last finalized, fully-synced block per contract so syncs only fetch new blocks.
+pub struct TransferCache {
+    conn: Mutex<Connection>,
+    finality_depth: u64,
+}
+
+impl TransferCache {
+    /// Opens (or creates) the cache db at path
+    pub fn open(path: &str) -> Result<TransferCache> {
+        info!("TransferCache::open|path={}", path);
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS transfers (
+                contract   TEXT    NOT NULL,
+                block_num  INTEGER NOT NULL,
+                seq        INTEGER NOT NULL,
+                block_hash TEXT    NOT NULL,
+                row        TEXT    NOT NULL,
+                PRIMARY KEY (contract, block_num, seq)
+            );
+            CREATE TABLE IF NOT EXISTS checkpoints (
+                contract     TEXT    PRIMARY KEY,
+                synced_block INTEGER NOT NULL
+            );",
+        )?;
+
+        Ok(TransferCache { conn: Mutex::new(conn), finality_depth: FINALITY_DEPTH })
+    }
+
+    /// Opens the cache described by the cache config section
+    pub fn from_config(config: &CacheConfig) -> Result<TransferCache> {
+        let cache = TransferCache::open(&config.path)?;
+
+        Ok(match config.finality_depth {
+            Some(x) => cache.finality_depth(x),
+            None => cache,
+        })
+    }
+
+    pub fn finality_depth(mut self, finality_depth: u64) -> TransferCache {
+        self.finality_depth = finality_depth.max(1);
+        self
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Last finalized block whose transfers are fully stored, None if the contract was never synced
+    pub fn checkpoint(&self, contract: &str) -> Result<Option<u64>> {
+        let block: Option<i64> = self.conn()
+            .query_row(
+                "SELECT synced_block FROM checkpoints WHERE contract = ?1",
+                params![contract.to_lowercase()],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(block.map(|x| x as u64))
+    }
+
+    /// Every stored row of a contract in chain order
+    pub fn rows(&self, contract: &str) -> Result<Vec<serde_json::Value>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT row FROM transfers WHERE contract = ?1 ORDER BY block_num, seq")?;
+
+        let rows = stmt.query_map(params![contract.to_lowercase()], |row| row.get::<_, String>(0))?;
+
+        let mut out = vec![];
+        for row in rows {
+            out.push(serde_json::from_str(&row?)?);
+        }
+        Ok(out)
+    }
+
+    /// Fetches the blocks after the checkpoint, replaces provisional rows from the previous
+    /// sync so reorged transfers drop out, and returns every stored row of the contract
+    pub async fn sync(&self, es_client: &EtherscanClient, contract: &str) -> Result<Vec<serde_json::Value>> {
+        let from_block = self.checkpoint(contract)?.map_or(0, |x| x + 1);
+        info!("TransferCache::sync|contract={} from_block={}", contract, from_block);
+
+        let new_transfers = es_client.erc721_transfer_history(contract, from_block, None).await?;
+        let synced_block = self.finalized_block(contract, &new_transfers)?;
+
+        let reorged = self.replace_from(contract, from_block, &new_transfers, synced_block)?;
+        if !reorged.is_empty() {
+            warn!("TransferCache::sync|reorged blocks replaced: {:?}", reorged);
+        }
+
+        self.rows(contract)
+    }
+
+    /// Last block that is final for a freshly fetched set of rows, None if there are no rows.
+    /// Etherscan reports confirmations = head - block + 1, so every block at or below
+    /// head + 1 - finality_depth has at least finality_depth confirmations.
+    pub fn finalized_block(&self, contract: &str, rows: &[serde_json::Value]) -> Result<Option<u64>> {
+        let mut head: Option<u64> = None;
+
+        for (i, row) in rows.iter().enumerate() {
+            let field = |name: &'static str| row[name].as_str()
+                .and_then(|x| x.parse::<u64>().ok())
+                .ok_or_else(|| Error::MalformedTransfer { contract: String::from(contract), row: i, field: name });
+
+            let block_head = field("blockNumber")? + field("confirmations")?.max(1) - 1;
+            head = head.max(Some(block_head));
+        }
+
+        Ok(head.and_then(|x| (x + 1).checked_sub(self.finality_depth)))
+    }
+
+    /// Replaces every stored row at or after from_block with the refetched rows and advances
+    /// the checkpoint to synced_block. Rows before from_block are final and left untouched.
+    /// Returns the provisional blocks whose block_hash changed or which disappeared (reorged).
+    pub fn replace_from(
+        &self,
+        contract: &str,
+        from_block: u64,
+        rows: &[serde_json::Value],
+        synced_block: Option<u64>,
+    ) -> Result<Vec<u64>> {
+        let contract = contract.to_lowercase();
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let mut stored: BTreeMap<u64, String> = BTreeMap::new();
+        {
+            let mut stmt = tx.prepare(
+                "SELECT DISTINCT block_num, block_hash FROM transfers WHERE contract = ?1 AND block_num >= ?2",
+            )?;
+            let blocks = stmt.query_map(params![contract, from_block as i64], |row| {
+                Ok((row.get::<_, i64>(0)? as u64, row.get::<_, String>(1)?))
+            })?;
+            for block in blocks {
+                let (block_num, block_hash) = block?;
+                stored.insert(block_num, block_hash);
+            }
+        }
+
+        tx.execute(
+            "DELETE FROM transfers WHERE contract = ?1 AND block_num >= ?2",
+            params![contract, from_block as i64],
+        )?;
+
+        let mut fetched: BTreeMap<u64, String> = BTreeMap::new();
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO transfers (contract, block_num, seq, block_hash, row)
+                 VALUES (?1, ?2, COALESCE((SELECT MAX(seq) + 1 FROM transfers WHERE contract = ?1 AND block_num = ?2), 0), ?3, ?4)",
+            )?;
+
+            for (i, row) in rows.iter().enumerate() {
+                let block = row["blockNumber"].as_str()
+                    .and_then(|x| x.parse::<u64>().ok())
+                    .ok_or_else(|| Error::MalformedTransfer { contract: contract.clone(), row: i, field: "blockNumber" })?;
+                let block_hash = row["blockHash"].as_str()
+                    .ok_or_else(|| Error::MalformedTransfer { contract: contract.clone(), row: i, field: "blockHash" })?;
+
+                stmt.execute(params![contract, block as i64, block_hash, row.to_string()])?;
+                fetched.insert(block, String::from(block_hash));
+            }
+        }
+
+        if let Some(synced_block) = synced_block {
+            tx.execute(
+                "INSERT INTO checkpoints (contract, synced_block) VALUES (?1, ?2)
+                 ON CONFLICT(contract) DO UPDATE SET synced_block = MAX(synced_block, excluded.synced_block)",
+                params![contract, synced_block as i64],
+            )?;
+        }
+        tx.commit()?;
+
+        let reorged: Vec<u64> = stored.into_iter()
+            .filter(|(block, hash)| fetched.get(block) != Some(hash))
+            .map(|(block, _)| block)
+            .collect();
+
+        info!("TransferCache::replace_from|contract={} from_block={} rows={} synced_block={:?} reorged={}",
+              contract, from_block, rows.len(), synced_block, reorged.len());
+        Ok(reorged)
+    }
+}
@@ -0,0 +1,163 @@
+use std::fs::{self, File};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use log::info;
+
+use plotly::layout::Layout;
+use plotly::{ImageFormat, Plot};
+
+use conf::ChartConfig;
+
+use crate::{Error, Result};
+
+pub const CHART_WIDTH: usize = 1200;
+pub const CHART_HEIGHT: usize = 800;
+pub const CHART_SCALE: f64 = 1.0;
+
+/// Chart file formats. Html is written by plotly itself, images are rendered by kaleido.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChartFormat {
+    Html,
+    Png,
+    Svg,
+    Jpeg,
+}
+
+impl ChartFormat {
+    /// Picks the format from the file extension
+    pub fn from_path(path: &str) -> Result<ChartFormat> {
+        let ext = Path::new(path).extension().and_then(|x| x.to_str()).unwrap_or("");
+
+        ChartFormat::from_str(ext).map_err(|e| match e {
+            Error::InvalidArgument { reason, .. } => Error::InvalidArgument {
+                name: String::from("path"),
+                value: String::from(path),
+                reason,
+            },
+            e => e,
+        })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChartFormat::Html => "html",
+            ChartFormat::Png => "png",
+            ChartFormat::Svg => "svg",
+            ChartFormat::Jpeg => "jpeg",
+        }
+    }
+}
+
+impl FromStr for ChartFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ChartFormat> {
+        match s.to_lowercase().as_str() {
+            "html" | "htm" => Ok(ChartFormat::Html),
+            "png" => Ok(ChartFormat::Png),
+            "svg" => Ok(ChartFormat::Svg),
+            "jpeg" | "jpg" => Ok(ChartFormat::Jpeg),
+            _ => Err(Error::InvalidArgument {
+                name: String::from("format"),
+                value: String::from(s),
+                reason: String::from("expected html, png, svg or jpeg"),
+            }),
+        }
+    }
+}
+
+/// Format and size a chart is written with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChartOptions {
+    pub format: ChartFormat,
+    pub width: usize,
+    pub height: usize,
+    pub scale: f64,
+}
+
+impl Default for ChartOptions {
+    fn default() -> ChartOptions {
+        ChartOptions { format: ChartFormat::Png, width: CHART_WIDTH, height: CHART_HEIGHT, scale: CHART_SCALE }
+    }
+}
+
+impl ChartOptions {
+    pub fn from_config(config: &ChartConfig) -> Result<ChartOptions> {
+        Ok(ChartOptions {
+            format: ChartFormat::from_str(&config.format)?,
+            width: config.width.unwrap_or(CHART_WIDTH),
+            height: config.height.unwrap_or(CHART_HEIGHT),
+            scale: config.scale.unwrap_or(CHART_SCALE),
+        })
+    }
+
+    pub fn format(mut self, format: ChartFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: usize) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Sizes a plot layout to width x height, html charts take their size from it
+    pub fn layout(&self, layout: Layout) -> Layout {
+        layout.width(self.width).height(self.height)
+    }
+}
+
+/// Utility fn to write a plot to path, returning the path written. Html is written at the size
+/// of the plot layout, see ChartOptions::layout. Kaleido panics when its binary is missing and
+/// stays silent when rendering fails, both are turned into Error::Plot.
+pub fn save_plot(plot: &Plot, title: &str, path: &Path, options: &ChartOptions) -> Result<PathBuf> {
+    info!("save_plot|path={} options={:?}", path.display(), options);
+
+    let plot_err = |message: String| Error::Plot { title: String::from(title), message };
+
+    let image_format = match options.format {
+        ChartFormat::Html => {
+            let mut file = File::create(path)?;
+            plot.write_html(&mut file);
+            return Ok(path.to_path_buf());
+        },
+        ChartFormat::Png => ImageFormat::PNG,
+        ChartFormat::Svg => ImageFormat::SVG,
+        ChartFormat::Jpeg => ImageFormat::JPEG,
+    };
+
+    // kaleido swaps the extension for the format name, e.g. chart.jpg becomes chart.jpeg
+    let rendered = path.with_extension(options.format.extension());
+    if rendered.exists() {
+        fs::remove_file(&rendered)?;
+    }
+
+    catch_unwind(AssertUnwindSafe(|| {
+        plot.save(path, image_format, options.width, options.height, options.scale)
+    })).map_err(|e| {
+        let reason = e.downcast_ref::<String>().cloned()
+            .or_else(|| e.downcast_ref::<&str>().map(|x| String::from(*x)))
+            .unwrap_or_default();
+        plot_err(format!("kaleido failed: {}", reason))
+    })?;
+
+    if !rendered.exists() {
+        return Err(plot_err(format!("kaleido did not render {}, check its install", rendered.display())));
+    }
+    if rendered != path {
+        fs::rename(&rendered, path)?;
+    }
+
+    Ok(path.to_path_buf())
+}
@@ -0,0 +1,141 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use log::info;
+
+use polars::datatypes::DataType::{self, *};
+use polars::datatypes::TimeUnit::Milliseconds;
+use polars::frame::DataFrame;
+use polars::prelude::{CsvReader, CsvWriter, IntoSeries, JsonFormat, JsonReader, JsonWriter, ParquetReader, ParquetWriter};
+use polars::prelude::{SerReader, SerWriter};
+
+use crate::{Error, Result};
+
+/// Timestamp layout shared by the csv and json writers so exports of either format read back the same
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Column types of the get_erc721_transfers DataFrame, restored when a dataset is imported
+const TRANSFER_SCHEMA: [(&str, DataType); 18] = [
+    ("block_num", UInt64),
+    ("timestamp", Datetime(Milliseconds, None)),
+    ("hash", Utf8),
+    ("nonce", UInt32),
+    ("block_hash", Utf8),
+    ("contract_address", Utf8),
+    ("to_address", Utf8),
+    ("from_address", Utf8),
+    ("token_id", UInt32),
+    ("token_name", Utf8),
+    ("token_symbol", Utf8),
+    ("token_decimal", Float64),
+    ("transaction_index", Utf8),
+    ("gas", Utf8),
+    ("gas_price", Float64),
+    ("gas_used", UInt32),
+    ("cumulative_gas_used", UInt64),
+    ("confirms", UInt64),
+];
+
+/// On-disk dataset formats. Json is newline-delimited, one object per row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    Csv,
+    Json,
+    Parquet,
+}
+
+impl DataFormat {
+    /// Picks the format from the file extension
+    pub fn from_path(path: &str) -> Result<DataFormat> {
+        let ext = Path::new(path).extension().and_then(|x| x.to_str()).unwrap_or("");
+
+        DataFormat::from_str(ext).map_err(|e| match e {
+            Error::InvalidArgument { reason, .. } => Error::InvalidArgument {
+                name: String::from("path"),
+                value: String::from(path),
+                reason,
+            },
+            e => e,
+        })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DataFormat::Csv => "csv",
+            DataFormat::Json => "json",
+            DataFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for DataFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DataFormat> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(DataFormat::Csv),
+            "json" | "ndjson" | "jsonl" => Ok(DataFormat::Json),
+            "parquet" => Ok(DataFormat::Parquet),
+            _ => Err(Error::InvalidArgument {
+                name: String::from("format"),
+                value: String::from(s),
+                reason: String::from("expected csv, json or parquet"),
+            }),
+        }
+    }
+}
+
+/// Utility fn to write a DataFrame to path
+pub fn write_df(df: &mut DataFrame, path: &str, format: DataFormat) -> Result<()> {
+    info!("write_df|path={} format={:?} rows={}", path, format, df.height());
+
+    let file = File::create(path)?;
+    match format {
+        DataFormat::Csv => CsvWriter::new(file)
+            .with_date_format(Some(String::from("%Y-%m-%d")))
+            .with_timestamp_format(Some(String::from(TIMESTAMP_FORMAT)))
+            .finish(df)?,
+        DataFormat::Json => JsonWriter::new(file)
+            .with_json_format(JsonFormat::JsonLines)
+            .finish(df)?,
+        DataFormat::Parquet => ParquetWriter::new(file).finish(df)?,
+    }
+    Ok(())
+}
+
+/// Utility fn to read a DataFrame from path. Csv and json column types are inferred from the
+/// whole file, parquet keeps the types it was written with.
+pub fn read_df(path: &str, format: DataFormat) -> Result<DataFrame> {
+    info!("read_df|path={} format={:?}", path, format);
+
+    let df = match format {
+        DataFormat::Csv => CsvReader::from_path(path)?
+            .infer_schema(None)
+            .has_header(true)
+            .finish()?,
+        DataFormat::Json => JsonReader::new(BufReader::new(File::open(path)?))
+            .with_json_format(JsonFormat::JsonLines)
+            .infer_schema_len(None)
+            .finish()?,
+        DataFormat::Parquet => ParquetReader::new(File::open(path)?).finish()?,
+    };
+    Ok(df)
+}
+
+/// Utility fn to load an exported transfer dataset with the get_erc721_transfers column types
+pub fn read_transfers(path: &str) -> Result<DataFrame> {
+    let mut df = read_df(path, DataFormat::from_path(path)?)?;
+
+    for (name, dtype) in TRANSFER_SCHEMA.iter() {
+        match (df.column(name)?.dtype(), dtype) {
+            (Utf8, Datetime(tu, _)) => {
+                let tu = *tu;
+                df.try_apply(name, |s| Ok(s.utf8()?.as_datetime(None, tu)?.into_series()))?
+            },
+            _ => df.try_apply(name, |s| s.cast(dtype))?,
+        };
+    }
+
+    Ok(df)
+}
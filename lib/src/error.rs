@@ -0,0 +1,137 @@
+use std::fmt;
+
+use polars::error::PolarsError;
+use reqwest::StatusCode;
+
+/// Result type returned by every public fn in the lib crate
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Error type for the lib crate. Each variant carries enough context
+/// (contract, row, field, key) for a caller to decide whether to retry, skip or alert.
+#[derive(Debug)]
+pub enum Error {
+    /// Etherscan answered with status != 1 and a non rate-limit message
+    Etherscan { contract: String, message: String, result: String },
+    /// Etherscan rejected the call because the api key budget is exhausted
+    RateLimit { contract: String, message: String },
+    /// Etherscan returned no transfers for the contract
+    NoTransfers { contract: String },
+    /// A single block holds more transfers than two Etherscan query windows can return
+    DenseBlock { contract: String, block: u64 },
+    /// A transfer row is missing a field or the field is not a string
+    MalformedTransfer { contract: String, row: usize, field: &'static str },
+    /// A required configuration key is absent
+    MissingConfig { key: String },
+    /// A configuration value failed to parse
+    InvalidConfig { key: String, reason: String },
+    /// A cli value failed to parse or is out of range
+    InvalidArgument { name: String, value: String, reason: String },
+    /// A web3 / json-rpc call failed on the way to or from the node
+    Rpc { contract: String, message: String },
+    /// A chart could not be built or written
+    Plot { title: String, message: String },
+    /// A status template failed to parse or render
+    Template { command: String, message: String },
+    /// A status could not be published
+    Publish { message: String },
+    /// A stage of a pipeline failed
+    Stage { pipeline: String, stage: String, error: Box<Error> },
+    /// Transport level failure talking to an http api
+    Http(reqwest::Error),
+    /// A response body is not valid json
+    Json(serde_json::Error),
+    /// A DataFrame operation failed
+    DataFrame(PolarsError),
+    /// The local transfer cache failed
+    Cache(rusqlite::Error),
+    /// Local file read / write failed
+    Io(std::io::Error),
+}
+
+impl Error {
+    /// true if retrying the same call later may succeed
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::RateLimit { .. } | Error::Rpc { .. } => true,
+            Error::Stage { error, .. } => error.is_transient(),
+            Error::Http(e) => {
+                e.is_timeout() || e.is_connect()
+                    || e.status().is_some_and(|s| s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS)
+            },
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Etherscan { contract, message, result } => {
+                write!(f, "etherscan error: contract={} message={} result={}", contract, message, result)
+            },
+            Error::RateLimit { contract, message } => {
+                write!(f, "etherscan rate limit: contract={} message={}", contract, message)
+            },
+            Error::NoTransfers { contract } => write!(f, "no transfers found: contract={}", contract),
+            Error::DenseBlock { contract, block } => {
+                write!(f, "dense block: contract={} block={} exceeds the etherscan result window", contract, block)
+            },
+            Error::MalformedTransfer { contract, row, field } => {
+                write!(f, "malformed transfer: contract={} row={} field={}", contract, row, field)
+            },
+            Error::MissingConfig { key } => write!(f, "config: {} is missing", key),
+            Error::InvalidConfig { key, reason } => write!(f, "config: {} is invalid, {}", key, reason),
+            Error::InvalidArgument { name, value, reason } => {
+                write!(f, "cli: {}={} is invalid, {}", name, value, reason)
+            },
+            Error::Rpc { contract, message } => write!(f, "rpc error: contract={} message={}", contract, message),
+            Error::Plot { title, message } => write!(f, "plot error: title={} message={}", title, message),
+            Error::Template { command, message } => write!(f, "template error: command={} {}", command, message),
+            Error::Publish { message } => write!(f, "publish error: {}", message),
+            Error::Stage { pipeline, stage, error } => write!(f, "pipeline error: pipeline={} stage={} {}", pipeline, stage, error),
+            Error::Http(e) => write!(f, "http error: {}", e),
+            Error::Json(e) => write!(f, "json error: {}", e),
+            Error::DataFrame(e) => write!(f, "dataframe error: {}", e),
+            Error::Cache(e) => write!(f, "cache error: {}", e),
+            Error::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::DataFrame(e) => Some(e),
+            Error::Cache(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::Stage { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self { Error::Http(e) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::Json(e) }
+}
+
+impl From<PolarsError> for Error {
+    fn from(e: PolarsError) -> Self { Error::DataFrame(e) }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self { Error::Cache(e) }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self { Error::Io(e) }
+}
+
+impl From<egg_mode::error::Error> for Error {
+    fn from(e: egg_mode::error::Error) -> Self { Error::Publish { message: e.to_string() } }
+}
@@ -0,0 +1,298 @@
+use std::str::FromStr;
+use std::time::Duration;
+use log::{info, warn};
+
+use tokio::sync::Mutex;
+use tokio::time::{sleep_until, Instant};
+
+use conf::EtherscanConfig;
+
+use crate::{Error, Result};
+
+pub const ES_BASE_URL: &str = "https://api.etherscan.io/api";
+
+/// Etherscan free tier budget
+const ES_CALLS_PER_SEC: u32 = 5;
+const ES_MAX_RETRIES: u32 = 4;
+const ES_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Etherscan rejects queries whose page x offset exceeds 10,000 rows
+pub const ES_MAX_WINDOW: u64 = 10_000;
+const ES_PAGE_SIZE: u64 = 1_000;
+
+/// Etherscan api client shared by every command. Owns the api key and base url,
+/// spaces calls to stay inside the calls-per-second budget and retries
+/// rate-limit and 5xx responses with exponential backoff.
+pub struct EtherscanClient {
+    http: reqwest::Client,
+    api_key: String,
+    base_url: String,
+    interval: Duration,
+    max_retries: u32,
+    page_size: u64,
+    next_slot: Mutex<Instant>,
+}
+
+impl EtherscanClient {
+    pub fn new(api_key: &str) -> EtherscanClient {
+        EtherscanClient {
+            http: reqwest::Client::new(),
+            api_key: String::from(api_key),
+            base_url: String::from(ES_BASE_URL),
+            interval: Duration::from_secs(1) / ES_CALLS_PER_SEC,
+            max_retries: ES_MAX_RETRIES,
+            page_size: ES_PAGE_SIZE,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Builds a client from the etherscan config section
+    pub fn from_config(config: &EtherscanConfig) -> EtherscanClient {
+        let mut client = EtherscanClient::new(config.api_key.expose());
+
+        if let Some(value) = config.base_url.as_ref().filter(|x| !x.is_empty()) {
+            client = client.base_url(value);
+        }
+
+        if let Some(calls_per_sec) = config.calls_per_sec {
+            client = client.calls_per_sec(calls_per_sec);
+        }
+
+        client
+    }
+
+    pub fn base_url(mut self, base_url: &str) -> EtherscanClient {
+        self.base_url = String::from(base_url);
+        self
+    }
+
+    pub fn calls_per_sec(mut self, calls_per_sec: u32) -> EtherscanClient {
+        self.interval = Duration::from_secs(1) / calls_per_sec.max(1);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> EtherscanClient {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Rows per page, clamped to 1..=ES_MAX_WINDOW
+    pub fn page_size(mut self, page_size: u64) -> EtherscanClient {
+        self.page_size = page_size.clamp(1, ES_MAX_WINDOW);
+        self
+    }
+
+    /// Waits for the next free call slot
+    async fn throttle(&self) {
+        let mut next_slot = self.next_slot.lock().await;
+        let slot = (*next_slot).max(Instant::now());
+        *next_slot = slot + self.interval;
+        drop(next_slot);
+
+        sleep_until(slot).await;
+    }
+
+    /// Calls the api once and returns the result array. An empty result
+    /// ("No transactions found") is not an error. The api key is part of the url,
+    /// so the url is left out of http errors.
+    async fn call(&self, contract: &str, params: &[(&str, String)]) -> Result<Vec<serde_json::Value>> {
+        self.throttle().await;
+
+        let http_err = |e: reqwest::Error| Error::Http(e.without_url());
+        let response = self.http
+            .get(&self.base_url)
+            .query(params)
+            .query(&[("apikey", &self.api_key)])
+            .send()
+            .await
+            .map_err(http_err)?
+            .error_for_status()
+            .map_err(http_err)?
+            .text()
+            .await
+            .map_err(http_err)?;
+
+        let mut tmp: serde_json::Value = serde_json::from_str(&response)?;
+
+        let status = tmp["status"].as_str().unwrap_or_default();
+        let message = String::from(tmp["message"].as_str().unwrap_or_default());
+
+        info!("etherscan|status={}", status);
+        info!("etherscan|message={}", message);
+
+        let result = match tmp["result"].take() {
+            serde_json::Value::Array(x) => return Ok(x),
+            serde_json::Value::String(x) => x,
+            x => x.to_string(),
+        };
+
+        if message.starts_with("No transactions found") || message.starts_with("No records found") {
+            Ok(vec![])
+        }
+        else if result.to_lowercase().contains("rate limit") {
+            Err(Error::RateLimit { contract: String::from(contract), message: result })
+        }
+        else {
+            Err(Error::Etherscan { contract: String::from(contract), message, result })
+        }
+    }
+
+    /// Calls the api, retrying transient failures with exponential backoff
+    pub async fn get(&self, contract: &str, params: &[(&str, String)]) -> Result<Vec<serde_json::Value>> {
+        let mut attempt = 0;
+
+        loop {
+            match self.call(contract, params).await {
+                Err(e) if e.is_transient() && attempt < self.max_retries => {
+                    let backoff = ES_BACKOFF * 2u32.pow(attempt);
+                    warn!("etherscan|attempt={} backoff={:?} error={}", attempt, backoff, e);
+
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                },
+                x => return x,
+            }
+        }
+    }
+
+    /// Retrieves one page of tokennfttx rows for a contract in [start_block, end_block]
+    pub async fn erc721_transfers_page(
+        &self,
+        contract: &str,
+        start_block: u64,
+        end_block: Option<u64>,
+        page: u64,
+        offset: u64,
+    ) -> Result<Vec<serde_json::Value>> {
+        self.sorted_page(contract, start_block, end_block, page, offset, "asc").await
+    }
+
+    async fn sorted_page(
+        &self,
+        contract: &str,
+        start_block: u64,
+        end_block: Option<u64>,
+        page: u64,
+        offset: u64,
+        sort: &str,
+    ) -> Result<Vec<serde_json::Value>> {
+        let mut params = vec![
+            ("module", String::from("account")),
+            ("action", String::from("tokennfttx")),
+            ("contractaddress", String::from(contract)),
+            ("startblock", start_block.to_string()),
+            ("page", page.to_string()),
+            ("offset", offset.to_string()),
+            ("sort", String::from(sort)),
+        ];
+        if let Some(end_block) = end_block {
+            params.push(("endblock", end_block.to_string()));
+        }
+
+        self.get(contract, &params).await
+    }
+
+    /// Pages through [start_block, end_block] in sort order until Etherscan's page x offset
+    /// cap is hit. Returns the rows and whether the cap cut the window short.
+    async fn window(
+        &self,
+        contract: &str,
+        start_block: u64,
+        end_block: Option<u64>,
+        sort: &str,
+    ) -> Result<(Vec<serde_json::Value>, bool)> {
+        let mut window: Vec<serde_json::Value> = vec![];
+        let mut page = 1;
+
+        loop {
+            let batch = self.sorted_page(contract, start_block, end_block, page, self.page_size, sort).await?;
+            let full = batch.len() as u64 == self.page_size;
+            window.extend(batch);
+
+            if !full { return Ok((window, false)); }
+            if (page + 1) * self.page_size > ES_MAX_WINDOW { return Ok((window, true)); }
+            page += 1;
+        }
+    }
+
+    /// Retrieves every tokennfttx row for a contract in [from_block, to_block], each exactly once.
+    ///
+    /// Pages through a block window until Etherscan's page x offset cap is hit. A capped
+    /// window may end part way through its last block, so rows of that block are dropped
+    /// and the next window starts at it; rows of earlier blocks are complete and kept.
+    /// A capped window holding a single block is read by dense_block.
+    pub async fn erc721_transfer_history(
+        &self,
+        contract: &str,
+        from_block: u64,
+        to_block: Option<u64>,
+    ) -> Result<Vec<serde_json::Value>> {
+        info!("erc721_transfer_history|starting");
+
+        let mut rows: Vec<serde_json::Value> = vec![];
+        let mut start_block = from_block;
+
+        loop {
+            let (window, capped) = self.window(contract, start_block, to_block, "asc").await?;
+
+            info!("erc721_transfer_history|start_block={} rows={} capped={}", start_block, window.len(), capped);
+
+            if !capped {
+                rows.extend(window);
+                break;
+            }
+
+            let first_block = block_number(&window[0], contract, rows.len())?;
+            let last_block = block_number(&window[window.len()-1], contract, rows.len() + window.len() - 1)?;
+
+            if first_block == last_block {
+                rows.extend(self.dense_block(contract, last_block, window).await?);
+                start_block = last_block + 1;
+                continue;
+            }
+
+            for row in window {
+                if block_number(&row, contract, rows.len())? < last_block {
+                    rows.push(row);
+                }
+            }
+            start_block = last_block;
+        }
+
+        info!("erc721_transfer_history|completed");
+        Ok(rows)
+    }
+
+    /// Reads a block holding more rows than one window, head given by the capped ascending
+    /// window, tail by a descending one. The rows both windows hold are where the end of the
+    /// head lines up with the start of the tail and are kept once, so a transfer repeated
+    /// within the block is not mistaken for overlap. Windows that do not overlap (the block
+    /// holds over twice the window) or line up more than one way are a DenseBlock error.
+    async fn dense_block(
+        &self,
+        contract: &str,
+        block: u64,
+        head: Vec<serde_json::Value>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let (mut tail, _) = self.window(contract, block, Some(block), "desc").await?;
+        tail.reverse();
+
+        let overlaps: Vec<usize> = (1..=head.len().min(tail.len()))
+            .filter(|&n| head[head.len() - n..] == tail[..n])
+            .collect();
+
+        info!("erc721_transfer_history|dense block={} head={} tail={} overlaps={:?}", block, head.len(), tail.len(), overlaps);
+
+        match overlaps.as_slice() {
+            [n] => Ok(head.into_iter().chain(tail.into_iter().skip(*n)).collect()),
+            _ => Err(Error::DenseBlock { contract: String::from(contract), block }),
+        }
+    }
+}
+
+/// Utility fn to parse the blockNumber of a tokennfttx row
+fn block_number(transfer: &serde_json::Value, contract: &str, row: usize) -> Result<u64> {
+    transfer["blockNumber"].as_str()
+        .and_then(|x| u64::from_str(x).ok())
+        .ok_or_else(|| Error::MalformedTransfer { contract: String::from(contract), row, field: "blockNumber" })
+}
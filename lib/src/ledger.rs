@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::info;
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use conf::{Config, LedgerConfig};
+
+use crate::{Post, Publisher, Result};
+
+/// A published post as recorded in the ledger
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedgerEntry {
+    pub id: i64,
+    pub name: String,
+    pub collection: Option<String>,
+    pub publisher: String,
+    /// Unix seconds
+    pub posted_at: i64,
+    pub status: String,
+    pub metrics: serde_json::Value,
+    pub remote_id: Option<String>,
+}
+
+impl LedgerEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<LedgerEntry> {
+        let metrics: String = row.get(6)?;
+
+        Ok(LedgerEntry {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            collection: row.get(2)?,
+            publisher: row.get(3)?,
+            posted_at: row.get(4)?,
+            status: row.get(5)?,
+            metrics: serde_json::from_str(&metrics).unwrap_or(serde_json::Value::Null),
+            remote_id: row.get(7)?,
+        })
+    }
+}
+
+const ENTRY_COLUMNS: &str = "id, name, collection, publisher, posted_at, status, metrics, remote_id";
+
+/// Persistent SQLite record of every published post, per post name and publisher, used to
+/// skip posts that repeat the last one or come sooner than the minimum interval.
+pub struct Ledger {
+    conn: Mutex<Connection>,
+    min_interval: Duration,
+    intervals: BTreeMap<String, Duration>,
+    force: bool,
+}
+
+impl Ledger {
+    /// Opens (or creates) the ledger db at path
+    pub fn open(path: &str) -> Result<Ledger> {
+        info!("Ledger::open|path={}", path);
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS posts (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                name       TEXT    NOT NULL,
+                collection TEXT,
+                publisher  TEXT    NOT NULL,
+                posted_at  INTEGER NOT NULL,
+                status     TEXT    NOT NULL,
+                metrics    TEXT    NOT NULL,
+                remote_id  TEXT
+            );
+            CREATE INDEX IF NOT EXISTS posts_by_name ON posts (name, publisher, posted_at);",
+        )?;
+
+        Ok(Ledger { conn: Mutex::new(conn), min_interval: Duration::ZERO, intervals: BTreeMap::new(), force: false })
+    }
+
+    /// Opens the ledger described by the ledger config section
+    pub fn from_config(config: &LedgerConfig) -> Result<Ledger> {
+        let ledger = Ledger::open(&config.path)?
+            .min_interval(Duration::from_secs(config.min_interval_mins.unwrap_or(0) * 60));
+
+        Ok(config.intervals_mins.iter().fold(ledger, |ledger, (publisher, mins)| {
+            ledger.interval(publisher, Duration::from_secs(mins * 60))
+        }))
+    }
+
+    /// Least time between two posts of the same name to a publisher
+    pub fn min_interval(mut self, min_interval: Duration) -> Ledger {
+        self.min_interval = min_interval;
+        self
+    }
+
+    /// min_interval of one publisher
+    pub fn interval(mut self, publisher: &str, interval: Duration) -> Ledger {
+        self.intervals.insert(String::from(publisher), interval);
+        self
+    }
+
+    /// Record posts without skipping any, e.g. for --force
+    pub fn force(mut self, force: bool) -> Ledger {
+        self.force = force;
+        self
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Last post of a name to a publisher
+    pub fn last(&self, name: &str, publisher: &str) -> Result<Option<LedgerEntry>> {
+        let entry = self.conn()
+            .query_row(
+                &format!("SELECT {} FROM posts WHERE name = ?1 AND publisher = ?2 ORDER BY posted_at DESC, id DESC LIMIT 1", ENTRY_COLUMNS),
+                params![name, publisher],
+                LedgerEntry::from_row,
+            )
+            .optional()?;
+
+        Ok(entry)
+    }
+
+    /// Why a post should not go to a publisher, None if it should: its status or metrics
+    /// are those of the last post to the publisher, or it comes too soon after it
+    pub fn skip_reason(&self, post: &Post, publisher: &str) -> Result<Option<String>> {
+        let last = match self.last(&post.name, publisher)? {
+            Some(x) if !self.force => x,
+            _ => return Ok(None),
+        };
+
+        if last.status == post.status || (!post.metrics.is_null() && last.metrics == post.metrics) {
+            return Ok(Some(format!("no change since the post of {}", timestamp(last.posted_at))));
+        }
+
+        let interval = self.intervals.get(publisher).copied().unwrap_or(self.min_interval);
+        let next = last.posted_at + interval.as_secs() as i64;
+        if next > now() {
+            return Ok(Some(format!("last post was {}, next one is due {}", timestamp(last.posted_at), timestamp(next))));
+        }
+
+        Ok(None)
+    }
+
+    /// Records a post published to a publisher, returning the ledger id
+    pub fn record(&self, post: &Post, publisher: &str, remote_id: Option<&str>) -> Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO posts (name, collection, publisher, posted_at, status, metrics, remote_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![post.name, post.collection, publisher, now(), post.status, post.metrics.to_string(), remote_id],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Latest posts first, of one collection or all of them
+    pub fn history(&self, collection: Option<&str>, limit: usize) -> Result<Vec<LedgerEntry>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM posts WHERE ?1 IS NULL OR collection = ?1 ORDER BY posted_at DESC, id DESC LIMIT ?2",
+            ENTRY_COLUMNS,
+        ))?;
+
+        let entries = stmt.query_map(params![collection, limit as i64], LedgerEntry::from_row)?;
+        Ok(entries.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+}
+
+/// Publisher that records what it publishes in a Ledger and skips posts the ledger
+/// holds back, so running a command twice does not post the same status twice
+pub struct LedgeredPublisher {
+    publisher: Box<dyn Publisher>,
+    ledger: Arc<Ledger>,
+}
+
+impl LedgeredPublisher {
+    pub fn new(publisher: Box<dyn Publisher>, ledger: Arc<Ledger>) -> LedgeredPublisher {
+        LedgeredPublisher { publisher, ledger }
+    }
+}
+
+#[async_trait]
+impl Publisher for LedgeredPublisher {
+    fn name(&self) -> &str {
+        self.publisher.name()
+    }
+
+    async fn publish(&self, post: &Post) -> Result<Option<String>> {
+        if let Some(reason) = self.ledger.skip_reason(post, self.name())? {
+            info!("LedgeredPublisher::publish|publisher={} skipped: {}", self.name(), reason);
+            println!("{}: skipped {}, {}", self.name(), post.name, reason);
+            return Ok(None);
+        }
+
+        let remote_id = self.publisher.publish(post).await?;
+        self.ledger.record(post, self.name(), remote_id.as_deref())?;
+        Ok(remote_id)
+    }
+}
+
+/// Utility fn to put publishers behind the ledger of the config, if it has one.
+/// force records posts without skipping any.
+pub fn ledgered(config: &Config, publishers: Vec<Box<dyn Publisher>>, force: bool) -> Result<Vec<Box<dyn Publisher>>> {
+    let ledger = match &config.ledger {
+        Some(x) => Arc::new(Ledger::from_config(x)?.force(force)),
+        None => return Ok(publishers),
+    };
+
+    Ok(publishers.into_iter()
+        .map(|x| Box::new(LedgeredPublisher::new(x, ledger.clone())) as Box<dyn Publisher>)
+        .collect())
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// Utility fn to print unix seconds as a utc date time
+pub(crate) fn timestamp(secs: i64) -> String {
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|x| x.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| secs.to_string())
+}
@@ -1,8 +1,6 @@
-use std::collections::{BTreeMap, HashMap};
-use std::thread::sleep;
 use std::str::FromStr;
-use std::io::{stdout, Write};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
 use log::info;
 
 use polars::datatypes::DataType::*;
@@ -15,32 +13,103 @@ use web3::contract::{Contract, Options};
 use web3::types::{Address, U256};
 
 use plotly::common::Title;
-use plotly::layout::{Axis, BarMode, Layout, Legend, TicksDirection};
+use plotly::layout::{Axis, Layout};
 use plotly::{Bar, Plot};
 
-use egg_mode::{KeyPair, Token};
-use egg_mode::media::{media_types, upload_media, get_status, ProgressInfo};
-use egg_mode::tweet::DraftTweet;
+use conf::{Collection, Config, MaxSupply, OutputConfig, RpcConfig};
+
+mod cache;
+mod chart;
+mod dataset;
+mod error;
+mod etherscan;
+mod ledger;
+mod pipeline;
+mod publish;
+mod rpc;
+mod scheduler;
+mod server;
+mod source;
+mod status;
+
+pub use cache::{TransferCache, FINALITY_DEPTH};
+pub use chart::{save_plot, ChartFormat, ChartOptions, CHART_HEIGHT, CHART_SCALE, CHART_WIDTH};
+pub use dataset::{read_df, read_transfers, write_df, DataFormat};
+pub use error::{Error, Result};
+pub use etherscan::{EtherscanClient, ES_BASE_URL};
+pub use ledger::{ledgered, Ledger, LedgerEntry, LedgeredPublisher};
+pub use pipeline::{chart, compute_metrics, normalize, render, run_pipeline, Data};
+pub use publish::{collection_publishers, publish_all, publisher_from_config, tweet_length, wait_for_media};
+pub use publish::{DiscordPublisher, Outbox, Post, Publisher, TelegramPublisher, TwitterPublisher, WebhookPublisher};
+pub use publish::{MEDIA_TIMEOUT_SECS, TELEGRAM_API_URL, TWEET_MAX_LENGTH};
+pub use rpc::RpcTransferSource;
+pub use scheduler::{run_job, Job, Scheduler};
+pub use server::{refresh_cache, router, serve};
+pub use source::{CachedTransferSource, DatasetTransferSource, FileTransferSource, TransferSource};
+pub use status::{default_template, migration_vars, mint_vars, render_status, status_template, MIGRATION_TEMPLATE, MINT_ACTIVITY_TEMPLATE};
+
+/// Contract a command works on, a configured collection or any --address
+#[derive(Clone, Copy, Debug)]
+pub enum Target<'a> {
+    Collection(&'a Collection),
+    Address(&'a str),
+}
 
-type ResponseMap = HashMap<String, Vec<serde_json::Value>>;
+impl<'a> Target<'a> {
+    pub fn address(&self) -> &'a str {
+        match self {
+            Target::Collection(x) => &x.address,
+            Target::Address(x) => x,
+        }
+    }
 
-pub struct EggToken {
-    token: egg_mode::Token,
-}
+    /// First block worth fetching, the deployment block of a collection
+    pub fn from_block(&self) -> u64 {
+        match self {
+            Target::Collection(x) => x.deployment_block,
+            Target::Address(_) => 0,
+        }
+    }
 
-impl EggToken {
-    pub fn new(con_key: String, con_secret: String, access_key: String, access_secret: String) -> EggToken {
-        let connect_token = KeyPair::new(con_key, con_secret);
-        let access_token = KeyPair::new(access_key, access_secret);
+    /// Short name used for file names, the collection name or the address
+    pub fn name(&self) -> &'a str {
+        match self {
+            Target::Collection(x) => &x.name,
+            Target::Address(x) => x,
+        }
+    }
+}
 
-        EggToken { token: Token::Access {consumer: connect_token, access: access_token, } }
+/// Utility fn to read a string field from an Etherscan transfer row
+fn transfer_field(transfer: &serde_json::Value, field: &'static str, contract: &str, row: usize) -> Result<String> {
+    match transfer[field].as_str() {
+        Some(x) => Ok(String::from(x)),
+        None => Err(Error::MalformedTransfer { contract: String::from(contract), row, field }),
     }
 }
 
-/// Utility function that retrieves a list of ERC721 transfers using the Etherscan API 
-async fn get_erc721_transfers(contract_addr: &str, es_key: &str) -> Result<DataFrame, Box<dyn std::error::Error>> {
+/// Utility function that retrieves a list of ERC721 transfers using the Etherscan API.
+/// With a cache, only blocks after its last finalized block are fetched; provisional rows
+/// from the previous sync are replaced so reorged transfers drop out.
+pub async fn get_erc721_transfers(
+    es_client: &EtherscanClient,
+    cache: Option<&TransferCache>,
+    contract_addr: &str,
+) -> Result<DataFrame> {
     info!("get_erc721_transfers|starting");
 
+    let transfers = match cache {
+        Some(cache) => cache.sync(es_client, contract_addr).await?,
+        None => es_client.erc721_transfer_history(contract_addr, 0, None).await?,
+    };
+    let df = transfers_df(contract_addr, &transfers)?;
+
+    info!("get_erc721_transfers|completed");
+    Ok(df)
+}
+
+/// Utility function that builds the typed transfer DataFrame from Etherscan tokennfttx rows
+pub(crate) fn transfers_df(contract_addr: &str, transfers: &[serde_json::Value]) -> Result<DataFrame> {
     let mut token_dec_vec: Vec<String> = vec![];
     let mut txn_index_vec: Vec<String> = vec![];
     let mut nonce_vec: Vec<String> = vec![];
@@ -62,86 +131,50 @@ async fn get_erc721_transfers(contract_addr: &str, es_key: &str) -> Result<DataF
     let mut token_nm_vec: Vec<String> = vec![];
     let mut token_sym_vec: Vec<String> = vec![];
 
-    let mut start_block = 0;
-    let mut cur_block;
-    let mut flag = true;
-
-    while flag {
-        let url = format!("https://api.etherscan.io/api?module=account&action=tokennfttx&contractaddress={contract_addr}&startblock={start_block}&sort=asc&apikey={api_key}",
-                              contract_addr = contract_addr,
-                              start_block = start_block,
-                              api_key = es_key);
-        
-        let response = reqwest::get(url)
-            .await?
-            .text()
-            .await?;
-
-        let tmp: serde_json::Value = serde_json::from_str(&response)?;
-
-        let status = &tmp["status"];
-        let message = &tmp["message"];
-
-        info!("get_erc721_transfers|status={}", status);
-        info!("get_erc721_transfers|message={}", message);
-
-        let transfers = match tmp["result"].as_array() {
-            Some(x) => x,
-            _ => panic!("error: unable to parse result"),
-        };
-
-        cur_block = match transfers.get(0) {
-            Some(x) => i32::from_str(x["blockNumber"].as_str().unwrap()).unwrap(),
-            None => 0,
-        };
-
-        for transfer in transfers {
-                let block_num = String::from(transfer["blockNumber"].as_str().unwrap());
-                let ts = String::from(transfer["timeStamp"].as_str().expect("error: timeStamp is invalid"));
-                let hash = String::from(transfer["hash"].as_str().expect("error: hash is invalid"));
-                let nonce = String::from(transfer["nonce"].as_str().expect("error: nonce is invalid"));
-                let block_hash = String::from(transfer["blockHash"].as_str().expect("error: blockHash is invalid"));
-                let con_addr = String::from(transfer["contractAddress"].as_str().expect("error: contractAddress is invalid"));
-                let to_addr = String::from(transfer["to"].as_str().expect("error: to is invalid"));
-                let from_addr = String::from(transfer["from"].as_str().expect("error: from is invalid"));
-                let token_id = String::from(transfer["tokenID"].as_str().expect("error: tokenID is invalid"));
-                let token_nm = String::from(transfer["tokenName"].as_str().expect("error: tokenName is invalid"));
-                let token_sym = String::from(transfer["tokenSymbol"].as_str().expect("error: tokenSymbol is invalid"));
-                let token_dec = String::from(transfer["tokenDecimal"].as_str().expect("error: tokenDecimal is invalid"));
-                let txn_index = String::from(transfer["transactionIndex"].as_str().expect("error: transactionIndex is invalid"));
-                let gas = String::from(transfer["gas"].as_str().expect("error: gas is invalid"));
-                let gas_price = String::from(transfer["gasPrice"].as_str().expect("error: gasPrice is invalid"));
-                let gas_used = String::from(transfer["gasUsed"].as_str().expect("error: gasUsed is invalid"));
-                let cum_gas_used = String::from(transfer["cumulativeGasUsed"].as_str().expect("error: cumulativeGasUsed is invalid"));
-                let confirms = String::from(transfer["confirmations"].as_str().expect("error: confirmations is invalid"));
-
-                block_num_vec.push(block_num);
-                ts_vec.push(ts);
-                hash_vec.push(hash);
-                nonce_vec.push(nonce);
-                block_hash_vec.push(block_hash);
-                contract_addr_vec.push(con_addr);
-                to_addr_vec.push(to_addr);
-                from_addr_vec.push(from_addr);
-                token_id_vec.push(token_id);
-                token_nm_vec.push(token_nm);
-                token_sym_vec.push(token_sym);
-                token_dec_vec.push(token_dec);
-                txn_index_vec.push(txn_index);
-                gas_vec.push(gas);
-                gas_price_vec.push(gas_price);
-                gas_used_vec.push(gas_used);
-                cum_gas_used_vec.push(cum_gas_used);
-                confirms_vec.push(confirms);
-        }
-
-        cur_block = i32::from_str(&block_num_vec[block_num_vec.len()-1]).unwrap();
-
-        if start_block == cur_block { flag = false; }
-        else { start_block = cur_block; }
+    for transfer in transfers {
+        let row = block_num_vec.len();
+        let field = |name| transfer_field(transfer, name, contract_addr, row);
+
+        let block_num = field("blockNumber")?;
+        let ts = field("timeStamp")?;
+        let hash = field("hash")?;
+        let nonce = field("nonce")?;
+        let block_hash = field("blockHash")?;
+        let con_addr = field("contractAddress")?;
+        let to_addr = field("to")?;
+        let from_addr = field("from")?;
+        let token_id = field("tokenID")?;
+        let token_nm = field("tokenName")?;
+        let token_sym = field("tokenSymbol")?;
+        let token_dec = field("tokenDecimal")?;
+        let txn_index = field("transactionIndex")?;
+        let gas = field("gas")?;
+        let gas_price = field("gasPrice")?;
+        let gas_used = field("gasUsed")?;
+        let cum_gas_used = field("cumulativeGasUsed")?;
+        let confirms = field("confirmations")?;
+
+        block_num_vec.push(block_num);
+        ts_vec.push(ts);
+        hash_vec.push(hash);
+        nonce_vec.push(nonce);
+        block_hash_vec.push(block_hash);
+        contract_addr_vec.push(con_addr);
+        to_addr_vec.push(to_addr);
+        from_addr_vec.push(from_addr);
+        token_id_vec.push(token_id);
+        token_nm_vec.push(token_nm);
+        token_sym_vec.push(token_sym);
+        token_dec_vec.push(token_dec);
+        txn_index_vec.push(txn_index);
+        gas_vec.push(gas);
+        gas_price_vec.push(gas_price);
+        gas_used_vec.push(gas_used);
+        cum_gas_used_vec.push(cum_gas_used);
+        confirms_vec.push(confirms);
     }
 
-    if block_num_vec.len() == 0 { panic!("error: unable to create vectors. dataframe creation aborted"); }
+    if block_num_vec.is_empty() { return Err(Error::NoTransfers { contract: String::from(contract_addr) }); }
 
     let mut df = DataFrame::new(vec![
         Series::new("block_num", &block_num_vec),
@@ -164,13 +197,11 @@ async fn get_erc721_transfers(contract_addr: &str, es_key: &str) -> Result<DataF
         Series::new("confirms", &confirms_vec),
     ])?;
 
-    df = df.distinct_stable(None, polars::frame::DistinctKeepStrategy::First)?;
-
     // format
     df.try_apply("block_num", |s: &Series| s.cast(&UInt64))?;
 
     df.try_apply("timestamp", |s: &Series| s.cast(&UInt64))?;
-    df.try_apply("timestamp", |s: &Series| Ok(s.u64()?.apply(|x| x * 1000).into_series()));
+    df.try_apply("timestamp", |s: &Series| Ok(s.u64()?.apply(|x| x * 1000).into_series()))?;
     df.try_apply("timestamp", |s: &Series| s.cast(&Datetime(Milliseconds, None)))?;
 
     df.try_apply("nonce", |s: &Series| s.cast(&UInt32))?;
@@ -181,19 +212,26 @@ async fn get_erc721_transfers(contract_addr: &str, es_key: &str) -> Result<DataF
     df.try_apply("cumulative_gas_used", |s: &Series| s.cast(&UInt64))?;
     df.try_apply("confirms", |s: &Series| s.cast(&UInt64))?;
 
-    info!("get_erc721_transfers|completed");
     Ok(df)
 }
 
 /// Utility method that calculates the daily mint activity
-async fn mint_act(
+pub async fn mint_act(
+    source: &dyn TransferSource,
     target_addr: &str,
-    es_key: &str,
-) -> Result<DataFrame, Box<dyn std::error::Error>> {
+) -> Result<DataFrame> {
 
     info!("mint_act|starting");
 
-    let mut df = get_erc721_transfers(&target_addr, &es_key).await?;
+    let df = source.fetch(target_addr, 0, None).await?;
+    let out = daily_mints(df)?;
+
+    info!("mint_act|completed");
+    Ok(out)
+}
+
+/// Utility method that groups a transfer DataFrame into daily mint counts
+pub fn daily_mints(mut df: DataFrame) -> Result<DataFrame> {
 
     // filter
     let mask: BooleanChunked = df.column("from_address")?
@@ -218,59 +256,106 @@ async fn mint_act(
     df = df.with_column(mint_s)?.filter(&mask)?;
 
     let mut out = df.select(vec!["timestamp", "token_name", "to_address", "from_address", "mint"])?;
-    out.try_apply("timestamp", |s: &Series| s.cast(&Date));
+    out.try_apply("timestamp", |s: &Series| s.cast(&Date))?;
 
     out = out.groupby(vec!["timestamp", "token_name"])?
             .select(vec!["mint"])
             .sum()?
             .sort(vec!["timestamp"], vec![false])?;
 
-    info!("mint_act|completed");
     Ok(out)
 }
 
-/// Utility method that calls the Honey Hives Deluxe contract to retrieve the MAX_SUPPLY
-async fn get_honey_supply(
-    http_provider: &str,
-    abi_path: &str,
-) -> Result<U256, Box<dyn std::error::Error>> {
-    const target_addr: &str = &"0x5df89cC648a6bd179bB4Db68C7CBf8533e8d796e";
+/// Utility method that works out the current holders from a transfer DataFrame in chain order,
+/// as every TransferSource returns it. Tokens sent to the zero address count as burned.
+pub fn holders(df: &DataFrame) -> Result<DataFrame> {
+    let token_ids = df.column("token_id")?.u32()?;
+    let to_addrs = df.column("to_address")?.utf8()?;
 
-    let http_transport = web3::transports::Http::new(http_provider)?;
-    let web3 = web3::Web3::new(http_transport);
-    let address_test = Address::from_str(&target_addr).unwrap();
-    let contract = Contract::from_json(web3.eth(), address_test, include_bytes!("HoneyHiveDeluxeAbi.json")).unwrap();
+    let mut owners: HashMap<u32, &str> = HashMap::new();
+    for (token_id, to_addr) in token_ids.into_iter().zip(to_addrs) {
+        if let (Some(token_id), Some(to_addr)) = (token_id, to_addr) {
+            owners.insert(token_id, to_addr);
+        }
+    }
 
-    let max_supply: U256 = contract
-        .query("MAX_SUPPLY", (), None, Options::default(), None)
-        .await
-        .unwrap();
+    let mut counts: HashMap<&str, u32> = HashMap::new();
+    for owner in owners.into_values().filter(|x| *x != ZERO_ADDRESS) {
+        *counts.entry(owner).or_insert(0) += 1;
+    }
 
-    Ok(max_supply)
+    let mut counts: Vec<(&str, u32)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let holder_vec: Vec<&str> = counts.iter().map(|x| x.0).collect();
+    let tokens_vec: Vec<u32> = counts.iter().map(|x| x.1).collect();
+
+    Ok(DataFrame::new(vec![
+        Series::new("holder", &holder_vec),
+        Series::new("tokens", &tokens_vec),
+    ])?)
+}
+
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+
+/// MAX_SUPPLY() subset of the collection abi, used when the collection has no abi_path
+const MAX_SUPPLY_ABI: &[u8] = br#"[
+    {"inputs":[],"name":"MAX_SUPPLY","outputs":[{"internalType":"uint256","name":"","type":"uint256"}],"stateMutability":"view","type":"function"}
+]"#;
+
+/// Utility fn to look up a collection by name in the collections config
+pub fn find_collection<'a>(collections: &'a [Collection], name: &str) -> Result<&'a Collection> {
+    collections.iter()
+        .find(|x| x.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| Error::InvalidArgument {
+            name: String::from("collection"),
+            value: String::from(name),
+            reason: String::from("not listed in the collections config"),
+        })
 }
 
-/// Utility method that calls the Bees Deluxe contract to retrieve the MAX_SUPPLY
-async fn get_bee_supply(
-    http_provider: &str,
-    abi_path: &str,
-) -> Result<U256, Box<dyn std::error::Error>> {
-    const target_addr: &str = &"0x1c2CD50f9Efb463bDd2ec9E36772c14A8D1658B3";
+/// Utility method that resolves the MAX_SUPPLY of a collection, calling the
+/// contract through the rpc node when the config says to read it on-chain
+pub async fn get_max_supply(rpc: Option<&RpcConfig>, collection: &Collection) -> Result<U256> {
+    if let MaxSupply::Fixed(x) = collection.max_supply {
+        return Ok(U256::from(x));
+    }
+
+    info!("get_max_supply|collection={} chain={}", collection.name, collection.chain);
+
+    // only a node that cannot be reached is worth retrying, the rest is down to the config
+    let invalid = |key: &str, reason: String| Error::InvalidConfig { key: format!("collections.{}.{}", collection.name, key), reason };
+
+    let abi = match &collection.abi_path {
+        Some(path) => std::fs::read(path)?,
+        None => MAX_SUPPLY_ABI.to_vec(),
+    };
 
-    let http_transport = web3::transports::Http::new(http_provider)?;
+    let rpc = rpc.ok_or_else(|| Error::MissingConfig { key: String::from("rpc.url") })?;
+    let http_transport = web3::transports::Http::new(rpc.url.expose()).map_err(|e| Error::InvalidConfig {
+        key: String::from("rpc.url"),
+        reason: e.to_string(),
+    })?;
     let web3 = web3::Web3::new(http_transport);
-    let address_test = Address::from_str(&target_addr).unwrap();
-    let contract = Contract::from_json(web3.eth(), address_test, include_bytes!("BeesDeluxeAbi.json")).unwrap();
+    let address = Address::from_str(&collection.address).map_err(|e| invalid("address", e.to_string()))?;
+    let contract = Contract::from_json(web3.eth(), address, &abi).map_err(|e| invalid("abi_path", e.to_string()))?;
 
     let max_supply: U256 = contract
         .query("MAX_SUPPLY", (), None, Options::default(), None)
         .await
-        .unwrap();
+        .map_err(|e| match e {
+            web3::contract::Error::Api(e @ (web3::Error::Transport(_) | web3::Error::Unreachable | web3::Error::Io(_))) => {
+                Error::Rpc { contract: collection.address.clone(), message: e.to_string() }
+            },
+            e => invalid("max_supply", format!("MAX_SUPPLY cannot be read, {}", e)),
+        })?;
 
     Ok(max_supply)
 }
 
-/// Utility method that takes a DataFrame object and creates a HTML plotly bar chart
-fn create_hist(df: DataFrame, title: &str) -> Result<(), Box<dyn std::error::Error>>{
+/// Utility method that takes a DataFrame object and writes a plotly bar chart to path,
+/// returning the path written
+fn create_hist(df: DataFrame, title: &str, path: &Path, options: &ChartOptions) -> Result<PathBuf> {
     info!("create_hist|starting");
     info!("create_hist|title={}", title);
 
@@ -280,7 +365,11 @@ fn create_hist(df: DataFrame, title: &str) -> Result<(), Box<dyn std::error::Err
 
     let mut domain_vec: Vec<String> = vec![];
     for date in &date_col {
-        domain_vec.push(String::from(date.unwrap()));
+        let date = date.ok_or_else(|| Error::Plot {
+            title: String::from(title),
+            message: String::from("timestamp contains a null date"),
+        })?;
+        domain_vec.push(String::from(date));
     }
 
     let mint_col = df.column("mint_sum")?
@@ -291,288 +380,270 @@ fn create_hist(df: DataFrame, title: &str) -> Result<(), Box<dyn std::error::Err
         mint_vec.push(mint.unwrap_or(0) as i32);
     }
 
-    let layout = Layout::new()
+    let layout = options.layout(Layout::new()
         .title(Title::new(title))
         .x_axis(Axis::new().title(Title::new("Date")))
-        .y_axis(Axis::new().title(Title::new("Mint Activity")));
+        .y_axis(Axis::new().title(Title::new("Mint Activity"))));
 
     let t = Bar::new(domain_vec, mint_vec);
 
     let mut plot = Plot::new();
     plot.set_layout(layout);
     plot.add_trace(t);
-    plot.show();
+    let path = save_plot(&plot, title, path, options)?;
 
     info!("create_hist|completed");
 
-    Ok(())
+    Ok(path)
 }
 
-/// Utility method to calculate the Bears Deluxe migration progress
-/// and POST status to twitter, if -t flag is enabled
-pub async fn bear_mint_act(
-    config: &BTreeMap<String, String>,
-    cli_args: clap::ArgMatches,
-) -> Result<(), Box<dyn std::error::Error>> {
-
-    info!("bear_mint_act|starting");
-
-    const ADDR: &str = "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B";
-    const MAX_SUPPLY: i32 = 6900;
+/// Utility method to work out the migration template variables of a collection
+pub async fn migration_status(
+    config: &Config,
+    source: &dyn TransferSource,
+    collection: &Collection,
+) -> Result<serde_json::Value> {
 
-    let es = config.get("es_key").expect("error: es_key is invalid");
-
-    let df = mint_act(ADDR, &es).await?;
-
-    let minted_today = df.column("mint_sum")?
-        .tail(Some(1))
-        .u32()?
-        .into_iter()
-        .next()
-        .unwrap()
-        .unwrap();
+    let target = Target::Collection(collection);
+    let df = normalize(source.fetch(&collection.address, collection.deployment_block, None).await?, 0)?;
 
-    let total_mint: i32 = df.column("mint_sum")?
-        .sum()
-        .unwrap();
+    compute_metrics(config.rpc.as_ref(), &target, &df, 0, "migration").await
+}
 
-    let remaining = MAX_SUPPLY - total_mint;
+/// Utility method to calculate the migration progress of a collection
+/// and publish the status to every publisher given
+pub async fn migration_act(
+    config: &Config,
+    source: &dyn TransferSource,
+    collection: &Collection,
+    publishers: &[Box<dyn Publisher>],
+) -> Result<()> {
 
-    let cent = (total_mint as f32/MAX_SUPPLY as f32)*100.0;
+    info!("migration_act|starting");
+    info!("migration_act|collection={}", collection.name);
 
-    let status = format!("- Bears Deluxe Migration -
-Progress: {:.2}%
-Migrated Today: {}
-Supply: {}/{}\n
-Remaining: {}",
-        cent,
-        minted_today,
-        total_mint,
-        MAX_SUPPLY,
-        remaining);
+    let template = status_template(config, Some(collection), "migration")?;
+    let vars = migration_status(config, source, collection).await?;
+    let status = render_status("migration", template, &vars)?;
 
     println!("{}", status);
 
-    let post_flg = i32::from_str(cli_args.value_of("post").unwrap_or("0")).unwrap();
-    match post_flg {
-        1 => { 
-            info!("bear_mint_act|post_flg={}", post_flg); 
-
-            let egg_token = EggToken::new(
-                String::from(config.get("con_key").expect("config: con_key is invalid")),
-                String::from(config.get("con_secret").expect("config: con_secret is invalid")),
-                String::from(config.get("acc_key").expect("config: acc_key is invalid")),
-                String::from(config.get("acc_secret").expect("config: acc_secret is invalid")),
-            );
-
-            send_tweet(&egg_token.token, status, None).await?;
+    match publishers.is_empty() {
+        true => info!("migration_act|skipping status POST"),
+        false => {
+            let post = Post::new(&format!("{}_migration", collection.name), status)
+                .collection(&collection.name)
+                .metrics(vars);
+            publish_all(publishers, &post).await?;
         },
-        _ => info!("bear_mint_act|skipping status POST"),
     }
 
-    info!("bear_mint_act|completed");
+    info!("migration_act|completed");
     Ok(())
 }
 
-pub async fn bee_mint_act(
-    config: &BTreeMap<String, String>,
-    cli_args: clap::ArgMatches,
-) -> Result<(), Box<dyn std::error::Error>> {
-
-    info!("bee_mint_act|starting");
+/// Utility method to work out the daily mints of a collection, or of any ERC721 contract
+/// given with --address, over the last lookback days (0 = all) and their template variables.
+/// Returns the windowed DataFrame along with the variables.
+pub async fn mint_activity_status(
+    source: &dyn TransferSource,
+    target: &Target<'_>,
+    lookback: u64,
+) -> Result<(DataFrame, serde_json::Value)> {
 
-    let target_addr = cli_args
-        .value_of("addr")
-        .unwrap_or("0x1c2CD50f9Efb463bDd2ec9E36772c14A8D1658B3");
+    info!("mint_activity_status|lookback={}", lookback);
 
-    let es = config.get("es_key").expect("error: es_key is invalid");
+    let df = normalize(source.fetch(target.address(), target.from_block(), None).await?, lookback)?;
+    let vars = compute_metrics(None, target, &df, lookback, "mint-activity").await?;
 
-    let mut df = mint_act(&target_addr, &es).await?;
+    Ok((df, vars))
+}
 
-    /*
-    let minted_today = df.column("mint_sum")?
-        .tail(Some(1))
-        .u32()?
-        .into_iter()
-        .next()
-        .unwrap()
-        .unwrap();
+/// Utility method to chart the daily mint activity of a collection, or of
+/// any ERC721 contract given with --address, over the last lookback days (0 = all).
+/// Without an output path the chart is written to the output dir of the config as
+/// <name>_mint_activity.<ext>. With publishers the chart is posted along with a summary.
+/// Returns the path of the chart.
+pub async fn erc721_mint_act(
+    config: &Config,
+    source: &dyn TransferSource,
+    options: &ChartOptions,
+    target: &Target<'_>,
+    lookback: u64,
+    output: Option<&str>,
+    publishers: &[Box<dyn Publisher>],
+) -> Result<PathBuf> {
 
-    let total_mint: i32 = df.column("mint_sum")?
-        .sum()
-        .unwrap();
+    info!("erc721_mint_act|starting");
 
-    let max_supply: U256 = get_bee_supply(config.get("alchemy_url").unwrap(), "BeesDeluxeAbi.json").await?;
+    let (output, options) = match output {
+        Some(output) => (PathBuf::from(output), options.format(ChartFormat::from_path(output)?)),
+        None => {
+            let stem = target.name().to_lowercase();
+            (Path::new(&config.output.dir).join(format!("{}_mint_activity.{}", stem, options.format.extension())), *options)
+        },
+    };
 
-    let remaining = max_supply - total_mint;
+    if !publishers.is_empty() && !matches!(options.format, ChartFormat::Png | ChartFormat::Jpeg) {
+        return Err(Error::InvalidArgument {
+            name: String::from("format"),
+            value: String::from(options.format.extension()),
+            reason: String::from("posted charts must be png or jpeg"),
+        });
+    }
 
-    let cent = (total_mint as f32/max_supply.low_u32() as f32)*100.0;
-    */
+    let collection = match target {
+        Target::Collection(x) => Some(*x),
+        Target::Address(_) => None,
+    };
+    let template = status_template(config, collection, "mint-activity")?;
 
-    let lookback = i32::from_str(cli_args.value_of("lookback")
-        .unwrap_or("0"))
-        .unwrap();
+    let (df, vars) = mint_activity_status(source, target, lookback).await?;
+    let status = render_status("mint-activity", template, &vars)?;
+    let title = vars["title"].as_str().unwrap_or_default();
 
-    let title = match lookback {
-        0 => {
-            info!("hive_mint_act|lookback={}", lookback);
-            String::from("Bees Deluxe Historical Mint Activity")
-        },
-        1..=180 => { 
-            info!("hive_mint_act|lookback={}", lookback);
-            df = df.tail(Some(lookback as usize));
-            format!("Bees Deluxe {}D Mint Activity", lookback)
+    let output = create_hist(df, title, &output, &options)?;
+    println!("{}", status);
+    println!("{}", output.display());
+
+    match publishers.is_empty() {
+        true => info!("erc721_mint_act|skipping status POST"),
+        false => {
+            let post = Post::new(&format!("{}_mint_activity", target.name().to_lowercase()), status)
+                .attach(&output)
+                .collection(target.name())
+                .metrics(vars);
+            publish_all(publishers, &post).await?;
         },
-        _ => panic!("error: lookback_days is invalid, lookback > 180"),
-    };
+    }
 
-    create_hist(df, &title);
+    info!("erc721_mint_act|completed");
 
-    info!("bee_mint_act|completed");
-    Ok(())
+    Ok(output)
 }
 
-pub async fn hive_mint_act(
-    config: &BTreeMap<String, String>,
-    cli_args: clap::ArgMatches,
-) -> Result<(), Box<dyn std::error::Error>> {
-
-    info!("hive_mint_act|starting");
-
-    let target_addr = cli_args
-        .value_of("addr")
-        .unwrap_or("0x5df89cC648a6bd179bB4Db68C7CBf8533e8d796e");
-
-    let es = config.get("es_key").expect("error: es_key is invalid");
+/// Utility method to preview the status of a command against live or cached data, rendered
+/// from template or else the configured one. Prints the status, its tweet length and with
+/// show_vars the template variables. Returns the status.
+pub async fn render_template_act(
+    config: &Config,
+    source: &dyn TransferSource,
+    command: &str,
+    target: &Target<'_>,
+    lookback: u64,
+    template: Option<&str>,
+    show_vars: bool,
+) -> Result<String> {
+
+    info!("render_template_act|starting");
+    info!("render_template_act|command={} target={}", command, target.name());
+
+    let collection = match target {
+        Target::Collection(x) => Some(*x),
+        Target::Address(_) => None,
+    };
+    let template = match template {
+        Some(x) => x,
+        None => status_template(config, collection, command)?,
+    };
 
-    let mut df = mint_act(&target_addr, &es).await?;
+    let vars = match (command, collection) {
+        ("migration", Some(collection)) => migration_status(config, source, collection).await?,
+        ("migration", None) => return Err(Error::InvalidArgument {
+            name: String::from("address"),
+            value: String::from(target.address()),
+            reason: String::from("migration needs a configured collection for its max supply"),
+        }),
+        _ => mint_activity_status(source, target, lookback).await?.1,
+    };
 
-    /*
-    let minted_today = df.column("mint_sum")?
-        .tail(Some(1))
-        .u32()?
-        .into_iter()
-        .next()
-        .unwrap()
-        .unwrap();
+    let status = render_status(command, template, &vars)?;
 
-    let total_mint: i32 = df.column("mint_sum")?
-        .sum()
-        .unwrap();
+    println!("{}", status);
+    println!("({}/{} characters)", tweet_length(&status), TWEET_MAX_LENGTH);
+    if show_vars {
+        println!("{}", serde_json::to_string_pretty(&vars)?);
+    }
 
-    let max_supply: U256 = get_honey_supply(config.get("alchemy_url").unwrap(), "HoneyHiveDeluxeAbi.json").await?;
+    info!("render_template_act|completed");
+    Ok(status)
+}
 
-    let remaining = max_supply - total_mint;
+/// Utility method to print the latest posts of the ledger, of one collection or all of them
+pub fn history_act(ledger: &Ledger, collection: Option<&str>, limit: usize) -> Result<Vec<LedgerEntry>> {
 
-    let cent = (total_mint as f32/max_supply.low_u32() as f32)*100.0;
-    */
+    info!("history_act|starting");
 
-    let lookback = i32::from_str(cli_args.value_of("lookback")
-        .unwrap_or("0"))
-        .unwrap();
+    let entries = ledger.history(collection, limit)?;
 
-    let title = match lookback {
-        0 => {
-            info!("hive_mint_act|lookback={}", lookback);
-            String::from("Honey Hives Deluxe Historical Mint Activity")
-        },
-        1..=180 => { 
-            info!("hive_mint_act|lookback={}", lookback);
-            df = df.tail(Some(lookback as usize));
-            format!("Honey Hives Deluxe {}D Mint Activity", lookback)
-        },
-        _ => panic!("error: lookback_days is invalid, lookback > 180"),
-    };
+    for entry in &entries {
+        println!("{} {} {} {}",
+            ledger::timestamp(entry.posted_at),
+            entry.publisher,
+            entry.name,
+            entry.remote_id.as_deref().unwrap_or("-"));
+        for line in entry.status.lines() {
+            println!("    {}", line);
+        }
+    }
 
-    create_hist(df, &title);
-    
-    info!("hive_mint_act|completed");
-    Ok(())
+    info!("history_act|completed");
+    Ok(entries)
 }
 
-pub async fn erc721_mint_act(
-    config: &BTreeMap<String, String>,
-    cli_args: clap::ArgMatches,
-) -> Result<(), Box<dyn std::error::Error>> {
+/// Utility method to print the number of holders of a collection or contract and the top holders
+pub async fn holders_act(
+    source: &dyn TransferSource,
+    target: &Target<'_>,
+    top: usize,
+) -> Result<()> {
 
-    info!("erc721_mint_act|starting");
+    info!("holders_act|starting");
+    info!("holders_act|target={} top={}", target.name(), top);
 
-    let target_addr = cli_args.value_of("addr").expect("error: addr is invalid");
-    let es = config.get("es_key").expect("error: es_key is invalid");
+    let df = holders(&source.fetch(target.address(), target.from_block(), None).await?)?;
+    let held: u32 = df.column("tokens")?.sum().unwrap_or(0);
 
-    let mut df = mint_act(&target_addr, &es).await?;
+    println!("{}: {} tokens held by {} holders", target.name(), held, df.height());
+    println!("{}", df.head(Some(top)));
 
-    let project_name = String::from(df.column("token_name")?
-        .utf8()?
-        .into_iter()
-        .next()
-        .unwrap()
-        .unwrap());
-
-    let lookback = i32::from_str(cli_args.value_of("lookback")
-        .unwrap_or("0"))
-        .unwrap();
-
-    let title = match lookback {
-        0 => {
-            info!("erc721_mint_act|lookback={}", lookback);
-            format!("{} Historical Mint Activity", project_name)
-        },
-        1..=180 => { 
-            info!("erc721_mint_act|lookback={}", lookback);
-            df = df.tail(Some(lookback as usize));
-            format!("{} {}D Mint Activity", project_name, lookback)
+    info!("holders_act|completed");
+    Ok(())
+}
+
+/// Utility method to write the transfer history of a collection or --address and its
+/// daily mint activity, the stats going next to it as <stem>_mint_act.<ext>. Without
+/// an output path the dataset is written to the output dir of the config.
+pub async fn export_act(
+    source: &dyn TransferSource,
+    output_config: &OutputConfig,
+    target: &Target<'_>,
+    output: Option<&str>,
+    format: Option<&str>,
+) -> Result<()> {
+
+    info!("export_act|starting");
+
+    let (output, format) = match (output, format) {
+        (Some(output), Some(format)) => (PathBuf::from(output), DataFormat::from_str(format)?),
+        (Some(output), None) => (PathBuf::from(output), DataFormat::from_path(output)?),
+        (None, format) => {
+            let format = DataFormat::from_str(format.unwrap_or(&output_config.format))?;
+            let stem = target.name().to_lowercase();
+            (Path::new(&output_config.dir).join(format!("{}.{}", stem, format.extension())), format)
         },
-        _ => panic!("error: lookback_days is invalid, lookback > 180"),
     };
+    info!("export_act|output={} format={:?}", output.display(), format);
 
-    create_hist(df, &title);
-
-    info!("erc721_mint_act|completed");
-
-    Ok(())
-}
+    let mut df = source.fetch(target.address(), target.from_block(), None).await?;
+    write_df(&mut df, &output.to_string_lossy(), format)?;
 
-/// Utility method to POST a tweet using egg_mode
-async fn send_tweet(
-    egg_token: &egg_mode::Token,
-    status: String,
-    file_path: Option<&String>,
-) -> Result<(), Box<dyn std::error::Error>>{
-    
-    info!("send_tweet|starting");
-    //info!("send_tweet|status={}", status);
-    //info!("send_tweet|file_path={}", file_path);
-
-    let mut tweet = DraftTweet::new(status);
-    
-    if file_path != None {
-        info!("send_tweet|uploading file: {}", file_path.unwrap());
-
-        let typ = media_types::image_png();
-        let bytes = std::fs::read(file_path.unwrap())?;
-
-        let handle = upload_media(&bytes, &typ, &egg_token).await?;
-        tweet.add_media(handle.id.clone());
-
-        info!("send_tweet|media upload processing...");
-        sleep(Duration::from_secs(30));
-
-        match get_status(handle.id.clone(), &egg_token).await?.progress {
-            None | Some(ProgressInfo::Success) => {
-                info!("send_tweet|media sucessfully processed");
-            }
-            Some(ProgressInfo::Pending(_)) | Some(ProgressInfo::InProgress(_)) => {
-                stdout().flush()?;
-                sleep(Duration::from_secs(45));
-            }
-            Some(ProgressInfo::Failed(err)) => Err(err)?,
-        }
-    }
-    
-    tweet.send(&egg_token).await?; 
+    let stem = output.file_stem().and_then(|x| x.to_str()).unwrap_or("transfers");
+    let stats_path = output.with_file_name(format!("{}_mint_act.{}", stem, format.extension()));
 
-    info!("send_tweet|completed");
+    let mut stats = daily_mints(df)?;
+    write_df(&mut stats, &stats_path.to_string_lossy(), format)?;
 
+    info!("export_act|completed");
     Ok(())
 }
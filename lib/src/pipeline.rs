@@ -0,0 +1,275 @@
+//! Pipelines. A pipeline of the pipelines section runs its stages against one collection
+//! in dependency order, each stage taking the Data of its inputs and giving one Data:
+//!
+//! - `ingest`: transfers of the collection from the TransferSource
+//! - `normalize`: daily mints of the transfers, over `lookback_days`
+//! - `holders`: current holders of the transfers
+//! - `metrics`: template variables of the daily mints, for `command` migration or mint-activity
+//! - `render`: status worded by `template` or the template of the metrics command
+//! - `chart`: mint activity chart of the daily mints, `<output.dir>/<collection>_<id>.<charts.format>`
+//! - `export`: transfers, daily mints or holders as `<output.dir>/<collection>_<id>.<format>`
+//! - `publish`: posts a status with the files of its other inputs attached
+//!
+//! The stage fns below work on their own, so other projects can reuse ingestion and
+//! metrics without publishing anything.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use log::info;
+
+use polars::frame::DataFrame;
+use serde_json::{json, Value};
+
+use conf::{Config, PipelineConfig, RpcConfig, StageConfig};
+
+use crate::{create_hist, daily_mints, find_collection, get_max_supply, holders, migration_vars, mint_vars, publish_all};
+use crate::{render_status, status_template, write_df};
+use crate::{ChartFormat, ChartOptions, DataFormat, Error, Post, Publisher, Result, Target, TransferSource};
+
+/// Data given by a stage, one variant per kind of PIPELINE_STAGES
+#[derive(Clone, Debug)]
+pub enum Data {
+    /// Transfer history, the get_erc721_transfers schema
+    Transfers(DataFrame),
+    /// daily_mints of the last lookback days, 0 = all
+    DailyMints { df: DataFrame, lookback: u64 },
+    Holders(DataFrame),
+    /// Template variables of a command
+    Metrics { command: String, vars: Value },
+    Status { command: String, status: String, vars: Value },
+    File(PathBuf),
+    /// Name of the post published
+    Post(String),
+}
+
+impl Data {
+    /// Kind of the data as named by PIPELINE_STAGES
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Data::Transfers(_) => "transfers",
+            Data::DailyMints { .. } => "daily_mints",
+            Data::Holders(_) => "holders",
+            Data::Metrics { .. } => "metrics",
+            Data::Status { .. } => "status",
+            Data::File(_) => "file",
+            Data::Post(_) => "post",
+        }
+    }
+}
+
+/// Stage fn keeping the daily mints of the last lookback days (0 = all) of a transfer DataFrame
+pub fn normalize(transfers: DataFrame, lookback: u64) -> Result<DataFrame> {
+    let df = daily_mints(transfers)?;
+
+    match lookback {
+        0 => Ok(df),
+        1..=180 => Ok(df.tail(Some(lookback as usize))),
+        _ => Err(Error::InvalidArgument {
+            name: String::from("lookback_days"),
+            value: lookback.to_string(),
+            reason: String::from("lookback > 180"),
+        }),
+    }
+}
+
+/// Stage fn working out the template variables of command from the daily mints of a target.
+/// migration needs a configured collection for its max supply, read through rpc if need be.
+pub async fn compute_metrics(
+    rpc: Option<&RpcConfig>,
+    target: &Target<'_>,
+    daily: &DataFrame,
+    lookback: u64,
+    command: &str,
+) -> Result<Value> {
+
+    let project_name = project_name(target, daily)?;
+
+    let mut vars = mint_vars(daily)?;
+    vars.insert(String::from("name"), json!(target.name()));
+    vars.insert(String::from("display_name"), json!(project_name));
+    vars.insert(String::from("title"), json!(mint_title(&project_name, lookback)));
+    vars.insert(String::from("lookback"), json!(lookback));
+
+    let vars = match (command, target) {
+        ("migration", Target::Collection(collection)) => {
+            migration_vars(vars, get_max_supply(rpc, collection).await?.low_u64())
+        },
+        ("migration", Target::Address(address)) => return Err(Error::InvalidArgument {
+            name: String::from("address"),
+            value: String::from(*address),
+            reason: String::from("migration needs a configured collection for its max supply"),
+        }),
+        _ => vars,
+    };
+
+    Ok(Value::Object(vars))
+}
+
+/// Stage fn wording the status of command from its variables, with template or else the configured one
+pub fn render(config: &Config, target: &Target<'_>, command: &str, template: Option<&str>, vars: &Value) -> Result<String> {
+    let collection = match target {
+        Target::Collection(x) => Some(*x),
+        Target::Address(_) => None,
+    };
+    let template = match template {
+        Some(x) => x,
+        None => status_template(config, collection, command)?,
+    };
+
+    render_status(command, template, vars)
+}
+
+/// Stage fn charting the daily mints of a target to path, returning the path written
+pub fn chart(target: &Target<'_>, daily: &DataFrame, lookback: u64, path: &Path, options: &ChartOptions) -> Result<PathBuf> {
+    let title = mint_title(&project_name(target, daily)?, lookback);
+    create_hist(daily.clone(), &title, path, options)
+}
+
+/// Utility fn naming the project of a target, the display name of a collection
+/// or the token name of the transfers of an address
+pub(crate) fn project_name(target: &Target<'_>, df: &DataFrame) -> Result<String> {
+    match target {
+        Target::Collection(x) => Ok(x.display_name.clone()),
+        Target::Address(x) => Ok(String::from(df.column("token_name")?
+            .utf8()?
+            .into_iter()
+            .next()
+            .flatten()
+            .ok_or_else(|| Error::NoTransfers { contract: String::from(*x) })?)),
+    }
+}
+
+/// Utility fn titling the mint activity of a project over lookback days
+pub(crate) fn mint_title(project_name: &str, lookback: u64) -> String {
+    match lookback {
+        0 => format!("{} Historical Mint Activity", project_name),
+        x => format!("{} {}D Mint Activity", project_name, x),
+    }
+}
+
+/// Utility method to run a pipeline of the config, each stage after its inputs. The status of
+/// a publish stage goes to every publisher given, none means no posting. Returns the data
+/// every stage gave, keyed by stage id.
+pub async fn run_pipeline(
+    config: &Config,
+    source: &dyn TransferSource,
+    pipeline: &PipelineConfig,
+    publishers: &[Box<dyn Publisher>],
+) -> Result<BTreeMap<String, Data>> {
+
+    info!("run_pipeline|starting");
+    info!("run_pipeline|pipeline={} collection={}", pipeline.name, pipeline.collection);
+
+    let collection = find_collection(&config.collections, &pipeline.collection)?;
+    let target = Target::Collection(collection);
+    let stages = pipeline.order().map_err(|ids| Error::InvalidConfig {
+        key: format!("pipelines.{}.stages", pipeline.name),
+        reason: format!("{} take each other as inputs", ids.join(", ")),
+    })?;
+
+    let mut outputs: BTreeMap<String, Data> = BTreeMap::new();
+    for stage in stages {
+        info!("run_pipeline|stage={} kind={}", stage.id, stage.stage);
+
+        let inputs: Vec<&Data> = stage.inputs.iter().filter_map(|x| outputs.get(x)).collect();
+        let data = run_stage(config, source, &target, pipeline, stage, &inputs, publishers).await
+            .map_err(|e| Error::Stage { pipeline: pipeline.name.clone(), stage: stage.id.clone(), error: Box::new(e) })?;
+
+        info!("run_pipeline|stage={} gave={}", stage.id, data.kind());
+        outputs.insert(stage.id.clone(), data);
+    }
+
+    info!("run_pipeline|completed");
+    Ok(outputs)
+}
+
+/// Runs one stage on the data of its inputs
+async fn run_stage(
+    config: &Config,
+    source: &dyn TransferSource,
+    target: &Target<'_>,
+    pipeline: &PipelineConfig,
+    stage: &StageConfig,
+    inputs: &[&Data],
+    publishers: &[Box<dyn Publisher>],
+) -> Result<Data> {
+
+    let mismatch = || Error::InvalidConfig {
+        key: format!("pipelines.{}.stages.{}.inputs", pipeline.name, stage.id),
+        reason: format!("{} cannot take [{}]", stage.stage, inputs.iter().map(|x| x.kind()).collect::<Vec<_>>().join(", ")),
+    };
+    let output = |extension: &str| {
+        Path::new(&config.output.dir).join(format!("{}_{}.{}", target.name().to_lowercase(), stage.id, extension))
+    };
+
+    match (stage.stage.as_str(), inputs) {
+        ("ingest", []) => Ok(Data::Transfers(source.fetch(target.address(), target.from_block(), None).await?)),
+        ("normalize", [Data::Transfers(df)]) => {
+            let lookback = stage.lookback_days.unwrap_or(0);
+            Ok(Data::DailyMints { df: normalize(df.clone(), lookback)?, lookback })
+        },
+        ("holders", [Data::Transfers(df)]) => Ok(Data::Holders(holders(df)?)),
+        ("metrics", [Data::DailyMints { df, lookback }]) => {
+            let command = stage.command.as_deref().unwrap_or("mint-activity");
+            let vars = compute_metrics(config.rpc.as_ref(), target, df, *lookback, command).await?;
+            Ok(Data::Metrics { command: String::from(command), vars })
+        },
+        ("render", [Data::Metrics { command, vars }]) => {
+            let status = render(config, target, command, stage.template.as_deref(), vars)?;
+            println!("{}", status);
+            Ok(Data::Status { command: command.clone(), status, vars: vars.clone() })
+        },
+        ("chart", [Data::DailyMints { df, lookback }]) => {
+            let options = ChartOptions::from_config(&config.charts)?;
+            let path = chart(target, df, *lookback, &output(options.format.extension()), &options)?;
+            println!("{}", path.display());
+            Ok(Data::File(path))
+        },
+        ("export", [Data::Transfers(df) | Data::DailyMints { df, .. } | Data::Holders(df)]) => {
+            let format = DataFormat::from_str(stage.format.as_deref().unwrap_or(&config.output.format))?;
+            let path = output(format.extension());
+            write_df(&mut df.clone(), &path.to_string_lossy(), format)?;
+            println!("{}", path.display());
+            Ok(Data::File(path))
+        },
+        ("publish", inputs) => {
+            let (status, vars) = match inputs.iter().find_map(|x| match x {
+                Data::Status { status, vars, .. } => Some((status, vars)),
+                _ => None,
+            }) {
+                Some(x) => x,
+                None => return Err(mismatch()),
+            };
+
+            let mut post = Post::new(&format!("{}_{}", target.name().to_lowercase(), pipeline.name), status.clone())
+                .collection(target.name())
+                .metrics(vars.clone());
+            for input in inputs.iter().filter(|x| !matches!(x, Data::Status { .. })) {
+                match input {
+                    Data::File(path) => post = post.attach(path),
+                    _ => return Err(mismatch()),
+                }
+            }
+
+            match publishers.is_empty() {
+                true => info!("run_stage|skipping status POST"),
+                false => {
+                    for path in &post.attachments {
+                        if !matches!(ChartFormat::from_path(&path.to_string_lossy()), Ok(ChartFormat::Png | ChartFormat::Jpeg)) {
+                            return Err(Error::InvalidArgument {
+                                name: String::from("attachment"),
+                                value: path.display().to_string(),
+                                reason: String::from("posted files must be png or jpeg charts"),
+                            });
+                        }
+                    }
+                    publish_all(publishers, &post).await?;
+                },
+            }
+
+            Ok(Data::Post(post.name))
+        },
+        _ => Err(mismatch()),
+    }
+}
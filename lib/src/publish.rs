@@ -0,0 +1,621 @@
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+
+use async_trait::async_trait;
+use egg_mode::{KeyPair, Token};
+use egg_mode::media::{media_types, upload_media, get_status, ProgressInfo};
+use egg_mode::tweet::DraftTweet;
+use reqwest::multipart::{Form, Part};
+use serde_json::{json, Value};
+
+use conf::{Collection, Config, OutputConfig, PublisherConfig, Secret, TwitterConfig};
+
+use crate::{ChartFormat, Error, Result};
+
+/// Default for twitter.media_timeout_secs
+pub const MEDIA_TIMEOUT_SECS: u64 = 300;
+
+/// Longest status twitter accepts, as counted by tweet_length
+pub const TWEET_MAX_LENGTH: usize = 280;
+
+/// Every link counts as this many characters once twitter wraps it in t.co
+const TWEET_URL_LENGTH: usize = 23;
+
+/// Most images a single tweet can carry
+const TWEET_MAX_ATTACHMENTS: usize = 4;
+
+/// Longest content of a discord message
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+/// Longest telegram message, and the shorter limit on a photo caption
+const TELEGRAM_MAX_LENGTH: usize = 4096;
+const TELEGRAM_MAX_CAPTION: usize = 1024;
+
+/// Bot API telegram publishers talk to unless api_url is set
+pub const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+/// Somewhere a post can be sent, e.g. twitter, a discord channel or the --dry-run outbox
+#[async_trait]
+pub trait Publisher: Send + Sync {
+    /// Name the publisher is configured under, used in logs and errors
+    fn name(&self) -> &str;
+
+    /// Publishes the post, returning the id the remote end gave it if there is one
+    async fn publish(&self, post: &Post) -> Result<Option<String>>;
+}
+
+/// Utility fn to count a status the way twitter does: links are 23 characters and
+/// anything outside latin, punctuation and symbols (CJK, emoji) counts twice
+pub fn tweet_length(status: &str) -> usize {
+    status.split_inclusive(char::is_whitespace)
+        .map(|piece| {
+            let word = piece.trim_end();
+            let space = piece[word.len()..].chars().count();
+
+            match word.starts_with("http://") || word.starts_with("https://") {
+                true => TWEET_URL_LENGTH + space,
+                false => piece.chars().map(|x| match x as u32 {
+                    0..=4351 | 8192..=8205 | 8208..=8223 | 8242..=8247 => 1,
+                    _ => 2,
+                }).sum(),
+            }
+        })
+        .sum()
+}
+
+/// A status and the images going with it. name tags the post in logs, outbox and ledger
+/// entries; metrics are the template variables the status was rendered from.
+#[derive(Clone, Debug)]
+pub struct Post {
+    pub name: String,
+    pub status: String,
+    pub attachments: Vec<PathBuf>,
+    pub collection: Option<String>,
+    pub metrics: Value,
+}
+
+impl Post {
+    pub fn new(name: &str, status: String) -> Post {
+        Post { name: String::from(name), status, attachments: vec![], collection: None, metrics: Value::Null }
+    }
+
+    pub fn attach(mut self, path: &Path) -> Self {
+        self.attachments.push(path.to_path_buf());
+        self
+    }
+
+    pub fn collection(mut self, collection: &str) -> Self {
+        self.collection = Some(String::from(collection));
+        self
+    }
+
+    pub fn metrics(mut self, metrics: Value) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn length(&self) -> usize {
+        tweet_length(&self.status)
+    }
+
+    pub fn fits(&self) -> bool {
+        self.length() <= TWEET_MAX_LENGTH
+    }
+}
+
+/// Utility fn to refuse a status longer than a publisher accepts, before anything is uploaded
+fn check_length(publisher: &str, post: &Post, length: usize, max_length: usize) -> Result<()> {
+    match length <= max_length {
+        true => Ok(()),
+        false => Err(Error::Publish {
+            message: format!("{}: {} is {} characters, {} allowed", publisher, post.name, length, max_length),
+        }),
+    }
+}
+
+/// Utility fn to read an attachment into a multipart file part
+async fn file_part(path: &Path) -> Result<Part> {
+    let file_name = path.file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .ok_or_else(|| Error::Publish { message: format!("attachment {} is not a file", path.display()) })?;
+    let mime = match ChartFormat::from_path(&file_name)? {
+        ChartFormat::Html => "text/html",
+        ChartFormat::Png => "image/png",
+        ChartFormat::Svg => "image/svg+xml",
+        ChartFormat::Jpeg => "image/jpeg",
+    };
+
+    Ok(Part::bytes(tokio::fs::read(path).await?).file_name(file_name).mime_str(mime)?)
+}
+
+/// Utility fn to read the id of a published message from a json answer, a string or a number
+fn remote_id(id: &Value) -> Option<String> {
+    match id {
+        Value::String(x) => Some(x.clone()),
+        Value::Number(x) => Some(x.to_string()),
+        _ => None,
+    }
+}
+
+/// Utility method to send a request, turning any non 2xx answer into Error::Publish.
+/// Webhook urls and bot tokens are credentials, so the url is left out of http errors.
+async fn send(publisher: &str, request: reqwest::RequestBuilder) -> Result<Value> {
+    let resp = request.send().await.map_err(|e| Error::Http(e.without_url()))?;
+    let status = resp.status();
+    let body = resp.text().await.map_err(|e| Error::Http(e.without_url()))?;
+
+    match status.is_success() {
+        true => Ok(serde_json::from_str(&body).unwrap_or(Value::Null)),
+        false => Err(Error::Publish { message: format!("{}: {} {}", publisher, status, body.trim()) }),
+    }
+}
+
+/// Tweets with egg_mode, uploading attachments as media
+pub struct TwitterPublisher {
+    name: String,
+    token: egg_mode::Token,
+    media_timeout: Duration,
+}
+
+impl TwitterPublisher {
+    pub fn new(con_key: String, con_secret: String, access_key: String, access_secret: String) -> TwitterPublisher {
+        let connect_token = KeyPair::new(con_key, con_secret);
+        let access_token = KeyPair::new(access_key, access_secret);
+
+        TwitterPublisher {
+            name: String::from("twitter"),
+            token: Token::Access {consumer: connect_token, access: access_token, },
+            media_timeout: Duration::from_secs(MEDIA_TIMEOUT_SECS),
+        }
+    }
+
+    pub fn from_config(config: &TwitterConfig) -> TwitterPublisher {
+        TwitterPublisher::new(
+            String::from(config.consumer_key.expose()),
+            String::from(config.consumer_secret.expose()),
+            String::from(config.access_key.expose()),
+            String::from(config.access_secret.expose()),
+        ).media_timeout(Duration::from_secs(config.media_timeout_secs.unwrap_or(MEDIA_TIMEOUT_SECS)))
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = String::from(name);
+        self
+    }
+
+    pub fn media_timeout(mut self, media_timeout: Duration) -> Self {
+        self.media_timeout = media_timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl Publisher for TwitterPublisher {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn publish(&self, post: &Post) -> Result<Option<String>> {
+        check_length(&self.name, post, post.length(), TWEET_MAX_LENGTH)?;
+        send_tweet(self, post.status.clone(), &post.attachments).await
+    }
+}
+
+/// Posts to a discord channel through a webhook, attachments go along as files
+pub struct DiscordPublisher {
+    name: String,
+    webhook_url: Secret,
+    username: Option<String>,
+    client: reqwest::Client,
+}
+
+impl DiscordPublisher {
+    pub fn new(webhook_url: &str) -> DiscordPublisher {
+        DiscordPublisher {
+            name: String::from("discord"),
+            webhook_url: Secret::new(webhook_url),
+            username: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = String::from(name);
+        self
+    }
+
+    /// Posts under this name instead of the one the webhook was created with
+    pub fn username(mut self, username: Option<&str>) -> Self {
+        self.username = username.map(String::from);
+        self
+    }
+}
+
+#[async_trait]
+impl Publisher for DiscordPublisher {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn publish(&self, post: &Post) -> Result<Option<String>> {
+        check_length(&self.name, post, post.status.chars().count(), DISCORD_MAX_LENGTH)?;
+
+        let mut payload = json!({ "content": post.status });
+        if let Some(username) = &self.username {
+            payload["username"] = json!(username);
+        }
+
+        // wait=true makes discord answer with the message, and so its id
+        let request = self.client.post(self.webhook_url.expose()).query(&[("wait", "true")]);
+        let request = match post.attachments.is_empty() {
+            true => request.json(&payload),
+            false => {
+                let mut form = Form::new().text("payload_json", payload.to_string());
+                for (i, path) in post.attachments.iter().enumerate() {
+                    form = form.part(format!("files[{}]", i), file_part(path).await?);
+                }
+                request.multipart(form)
+            },
+        };
+
+        Ok(remote_id(&send(&self.name, request).await?["id"]))
+    }
+}
+
+/// Posts to a telegram chat with the bot API. With attachments the status becomes the
+/// caption of the first photo, which telegram caps shorter than a message.
+pub struct TelegramPublisher {
+    name: String,
+    bot_token: Secret,
+    chat_id: String,
+    api_url: String,
+    client: reqwest::Client,
+}
+
+impl TelegramPublisher {
+    pub fn new(bot_token: &str, chat_id: &str) -> TelegramPublisher {
+        TelegramPublisher {
+            name: String::from("telegram"),
+            bot_token: Secret::new(bot_token),
+            chat_id: String::from(chat_id),
+            api_url: String::from(TELEGRAM_API_URL),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = String::from(name);
+        self
+    }
+
+    pub fn api_url(mut self, api_url: &str) -> Self {
+        self.api_url = String::from(api_url.trim_end_matches('/'));
+        self
+    }
+
+    fn method_url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", self.api_url, self.bot_token.expose(), method)
+    }
+}
+
+#[async_trait]
+impl Publisher for TelegramPublisher {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn publish(&self, post: &Post) -> Result<Option<String>> {
+        let length = post.status.chars().count();
+
+        if post.attachments.is_empty() {
+            check_length(&self.name, post, length, TELEGRAM_MAX_LENGTH)?;
+
+            let payload = json!({ "chat_id": self.chat_id, "text": post.status });
+            let resp = send(&self.name, self.client.post(self.method_url("sendMessage")).json(&payload)).await?;
+            return Ok(remote_id(&resp["result"]["message_id"]));
+        }
+
+        check_length(&self.name, post, length, TELEGRAM_MAX_CAPTION)?;
+
+        let mut message_id = None;
+        for (i, path) in post.attachments.iter().enumerate() {
+            let mut form = Form::new()
+                .text("chat_id", self.chat_id.clone())
+                .part("photo", file_part(path).await?);
+            if i == 0 {
+                form = form.text("caption", post.status.clone());
+            }
+            let resp = send(&self.name, self.client.post(self.method_url("sendPhoto")).multipart(form)).await?;
+            message_id = message_id.or_else(|| remote_id(&resp["result"]["message_id"]));
+        }
+
+        Ok(message_id)
+    }
+}
+
+/// POSTs the post as json to any url, attachments base64 encoded:
+/// {"name", "status", "length", "attachments": [{"file_name", "data"}]}
+pub struct WebhookPublisher {
+    name: String,
+    url: Secret,
+    headers: Vec<(String, Secret)>,
+    client: reqwest::Client,
+}
+
+impl WebhookPublisher {
+    pub fn new(url: &str) -> WebhookPublisher {
+        WebhookPublisher {
+            name: String::from("webhook"),
+            url: Secret::new(url),
+            headers: vec![],
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = String::from(name);
+        self
+    }
+
+    /// Adds a request header, e.g. Authorization
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((String::from(name), Secret::new(value)));
+        self
+    }
+}
+
+#[async_trait]
+impl Publisher for WebhookPublisher {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn publish(&self, post: &Post) -> Result<Option<String>> {
+        let mut attachments = vec![];
+        for path in &post.attachments {
+            attachments.push(json!({
+                "file_name": path.file_name().map(|x| x.to_string_lossy().into_owned()),
+                "data": base64::encode(tokio::fs::read(path).await?),
+            }));
+        }
+
+        let payload = json!({
+            "name": post.name,
+            "status": post.status,
+            "length": post.length(),
+            "attachments": attachments,
+        });
+
+        let mut request = self.client.post(self.url.expose()).json(&payload);
+        for (name, value) in &self.headers {
+            request = request.header(name.as_str(), value.expose());
+        }
+
+        Ok(remote_id(&send(&self.name, request).await?["id"]))
+    }
+}
+
+/// Directory --dry-run posts are written to, one sub directory per post
+pub struct Outbox {
+    dir: PathBuf,
+}
+
+impl Outbox {
+    pub fn new(dir: &str) -> Outbox {
+        Outbox { dir: PathBuf::from(dir) }
+    }
+
+    pub fn from_config(config: &OutputConfig) -> Outbox {
+        match &config.outbox {
+            Some(x) => Outbox::new(x),
+            None => Outbox { dir: Path::new(&config.dir).join("outbox") },
+        }
+    }
+
+    /// Writes status.txt, post.json with the length check and a copy of every
+    /// attachment to <dir>/<timestamp>-<name>, returning that directory
+    pub fn write(&self, post: &Post) -> Result<PathBuf> {
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+
+        let mut entry = self.dir.join(format!("{}-{}", stamp, post.name));
+        let mut n = 1;
+        while entry.exists() {
+            n += 1;
+            entry = self.dir.join(format!("{}-{}-{}", stamp, post.name, n));
+        }
+        fs::create_dir_all(&entry)?;
+
+        let mut attachments = vec![];
+        for path in &post.attachments {
+            let file_name = path.file_name().ok_or_else(|| Error::Publish {
+                message: format!("attachment {} is not a file", path.display()),
+            })?;
+            fs::copy(path, entry.join(file_name))?;
+            attachments.push(file_name.to_string_lossy().into_owned());
+        }
+
+        let summary = json!({
+            "name": post.name,
+            "status": post.status,
+            "length": post.length(),
+            "max_length": TWEET_MAX_LENGTH,
+            "fits": post.fits(),
+            "attachments": attachments,
+        });
+
+        fs::write(entry.join("status.txt"), &post.status)?;
+        fs::write(entry.join("post.json"), serde_json::to_string_pretty(&summary)?)?;
+
+        info!("outbox|wrote {}", entry.display());
+        Ok(entry)
+    }
+}
+
+/// Keeps the post for review instead of sending it. Posts too long to tweet are kept
+/// and flagged rather than refused.
+#[async_trait]
+impl Publisher for Outbox {
+    fn name(&self) -> &str {
+        "outbox"
+    }
+
+    async fn publish(&self, post: &Post) -> Result<Option<String>> {
+        let entry = self.write(post)?;
+        println!("dry run: {} ({}/{} characters{})",
+            entry.display(),
+            post.length(),
+            TWEET_MAX_LENGTH,
+            if post.fits() { "" } else { ", too long to tweet" });
+        Ok(Some(entry.to_string_lossy().into_owned()))
+    }
+}
+
+/// Utility fn to build the publisher configured under name in the publishers section
+pub fn publisher_from_config(config: &Config, name: &str) -> Result<Box<dyn Publisher>> {
+    let publisher = config.publishers.get(name)
+        .ok_or_else(|| Error::MissingConfig { key: format!("publishers.{}", name) })?;
+
+    Ok(match publisher {
+        PublisherConfig::Twitter => {
+            let twitter = config.twitter.as_ref()
+                .ok_or_else(|| Error::MissingConfig { key: String::from("twitter") })?;
+            Box::new(TwitterPublisher::from_config(twitter).named(name))
+        },
+        PublisherConfig::Discord { webhook_url, username } => Box::new(
+            DiscordPublisher::new(webhook_url.expose())
+                .named(name)
+                .username(username.as_deref())
+        ),
+        PublisherConfig::Telegram { bot_token, chat_id, api_url } => Box::new(
+            TelegramPublisher::new(bot_token.expose(), chat_id)
+                .named(name)
+                .api_url(api_url.as_deref().unwrap_or(TELEGRAM_API_URL))
+        ),
+        PublisherConfig::Webhook { url, headers } => Box::new(
+            headers.iter().fold(WebhookPublisher::new(url.expose()).named(name), |webhook, (header, value)| {
+                webhook.header(header, value.expose())
+            })
+        ),
+    })
+}
+
+/// Utility fn to build the publishers --post sends to: the ones a collection lists in
+/// publish, or the twitter section alone for collections listing none and bare addresses
+pub fn collection_publishers(config: &Config, collection: Option<&Collection>) -> Result<Vec<Box<dyn Publisher>>> {
+    match collection.filter(|x| !x.publish.is_empty()) {
+        Some(collection) => collection.publish.iter()
+            .map(|x| publisher_from_config(config, x))
+            .collect(),
+        None => {
+            let twitter = config.twitter.as_ref()
+                .ok_or_else(|| Error::MissingConfig { key: String::from("twitter") })?;
+            Ok(vec![Box::new(TwitterPublisher::from_config(twitter))])
+        },
+    }
+}
+
+/// Utility method to send a post to every publisher. A failing publisher does not stop
+/// the rest, the failures are returned together once all of them were tried.
+pub async fn publish_all(publishers: &[Box<dyn Publisher>], post: &Post) -> Result<()> {
+    let mut failed = vec![];
+
+    for publisher in publishers {
+        info!("publish_all|publisher={} name={} length={} attachments={}",
+            publisher.name(), post.name, post.length(), post.attachments.len());
+
+        match publisher.publish(post).await {
+            Ok(remote_id) => info!("publish_all|publisher={} remote_id={:?}", publisher.name(), remote_id),
+            Err(e) => {
+                warn!("publish_all|publisher={} failed: {}", publisher.name(), e);
+                failed.push(format!("{} ({})", publisher.name(), e));
+            },
+        }
+    }
+
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Publish { message: format!("{} not published to {}", post.name, failed.join(", ")) }),
+    }
+}
+
+/// Utility method that waits for twitter to finish processing uploaded media, sleeping
+/// for the check_after_secs of each ProgressInfo between calls to poll. Gives up with
+/// Error::Publish when processing fails or would not be done within timeout.
+pub async fn wait_for_media<F, Fut>(
+    mut progress: Option<ProgressInfo>,
+    timeout: Duration,
+    mut poll: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Option<ProgressInfo>>>,
+{
+    let started = Instant::now();
+
+    loop {
+        let check_after = match progress {
+            None | Some(ProgressInfo::Success) => {
+                info!("wait_for_media|media sucessfully processed");
+                return Ok(());
+            },
+            Some(ProgressInfo::Failed(err)) => return Err(Error::Publish { message: err.to_string() }),
+            Some(ProgressInfo::Pending(x)) | Some(ProgressInfo::InProgress(x)) => Duration::from_secs(x.max(1)),
+        };
+
+        if started.elapsed() + check_after > timeout {
+            return Err(Error::Publish {
+                message: format!("media still processing after {}s, timeout is {}s",
+                    started.elapsed().as_secs(), timeout.as_secs()),
+            });
+        }
+
+        info!("wait_for_media|media processing, checking again in {}s", check_after.as_secs());
+        tokio::time::sleep(check_after).await;
+        progress = poll().await?;
+    }
+}
+
+/// Utility method to POST a tweet using egg_mode, returning the tweet id
+async fn send_tweet(
+    twitter: &TwitterPublisher,
+    status: String,
+    attachments: &[PathBuf],
+) -> Result<Option<String>> {
+
+    info!("send_tweet|starting");
+
+    if attachments.len() > TWEET_MAX_ATTACHMENTS {
+        return Err(Error::Publish {
+            message: format!("{} attachments, a tweet carries at most {}", attachments.len(), TWEET_MAX_ATTACHMENTS),
+        });
+    }
+
+    let mut tweet = DraftTweet::new(status);
+
+    for file_path in attachments {
+        info!("send_tweet|uploading file: {}", file_path.display());
+
+        let typ = match ChartFormat::from_path(&file_path.to_string_lossy())? {
+            ChartFormat::Jpeg => media_types::image_jpg(),
+            _ => media_types::image_png(),
+        };
+        let bytes = tokio::fs::read(file_path).await?;
+
+        let handle = upload_media(&bytes, &typ, &twitter.token).await?;
+        tweet.add_media(handle.id.clone());
+
+        info!("send_tweet|media upload processing...");
+        wait_for_media(handle.progress, twitter.media_timeout, || async {
+            Ok(get_status(handle.id.clone(), &twitter.token).await?.progress)
+        }).await?;
+    }
+
+    let sent = tweet.send(&twitter.token).await?;
+
+    info!("send_tweet|completed");
+
+    Ok(Some(sent.id.to_string()))
+}
@@ -0,0 +1,172 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use log::{info, warn};
+
+use serde_json::json;
+
+use web3::contract::{Contract, Options};
+use web3::transports::Http;
+use web3::types::{Address, BlockId, BlockNumber, FilterBuilder, H256, U256, U64};
+
+use conf::RpcConfig;
+
+use crate::{Error, Result};
+
+/// Initial and maximum eth_getLogs block ranges
+const RPC_CHUNK_SIZE: u64 = 2_000;
+const RPC_MAX_CHUNK_SIZE: u64 = 100_000;
+
+/// A chunk returning fewer logs than this doubles the next block range
+const RPC_SPARSE_LOGS: usize = 1_000;
+
+/// name() / symbol() subset of the ERC721 metadata abi
+const ERC721_METADATA_ABI: &[u8] = br#"[
+    {"inputs":[],"name":"name","outputs":[{"internalType":"string","name":"","type":"string"}],"stateMutability":"view","type":"function"},
+    {"inputs":[],"name":"symbol","outputs":[{"internalType":"string","name":"","type":"string"}],"stateMutability":"view","type":"function"}
+]"#;
+
+/// Reads ERC721 Transfer(address,address,uint256) events straight from a json-rpc node.
+///
+/// Logs are scanned with eth_getLogs in adaptive block ranges: a failed range (most
+/// providers cap results per call) is halved and retried, a sparse one doubles the next.
+/// Rows have the Etherscan tokennfttx shape but nonce, gas, gasPrice, gasUsed and
+/// cumulativeGasUsed are left "", since they would cost a transaction and a receipt lookup
+/// per transfer: in the DataFrame gas is empty and nonce, gas_price, gas_used and
+/// cumulative_gas_used are null. Use the etherscan source when those columns matter.
+pub struct RpcTransferSource {
+    web3: web3::Web3<Http>,
+    chunk_size: u64,
+}
+
+impl RpcTransferSource {
+    pub fn new(http_provider: &str) -> Result<RpcTransferSource> {
+        let http_transport = Http::new(http_provider).map_err(|e| Error::InvalidConfig {
+            key: String::from("rpc.url"),
+            reason: e.to_string(),
+        })?;
+
+        Ok(RpcTransferSource { web3: web3::Web3::new(http_transport), chunk_size: RPC_CHUNK_SIZE })
+    }
+
+    /// Builds a source from the rpc config section
+    pub fn from_config(config: &RpcConfig) -> Result<RpcTransferSource> {
+        RpcTransferSource::new(config.url.expose())
+    }
+
+    pub fn chunk_size(mut self, chunk_size: u64) -> RpcTransferSource {
+        self.chunk_size = chunk_size.clamp(1, RPC_MAX_CHUNK_SIZE);
+        self
+    }
+
+    /// Retrieves every Transfer event of a contract in [from_block, to_block] as tokennfttx shaped rows
+    pub async fn transfer_rows(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<Vec<serde_json::Value>> {
+        info!("RpcTransferSource::transfer_rows|starting");
+
+        let rpc_err = |message: String| Error::Rpc { contract: String::from(contract), message };
+
+        let address: Address = contract.parse().map_err(|_| Error::InvalidArgument {
+            name: String::from("address"),
+            value: String::from(contract),
+            reason: String::from("is not a contract address"),
+        })?;
+        let transfer_topic = H256::from(web3::signing::keccak256(b"Transfer(address,address,uint256)"));
+
+        let head = self.web3.eth().block_number().await.map_err(|e| rpc_err(e.to_string()))?.as_u64();
+        let to_block = to_block.unwrap_or(head).min(head);
+
+        let (token_name, token_symbol) = self.metadata(address).await;
+
+        let mut rows: Vec<serde_json::Value> = vec![];
+        let mut timestamps: HashMap<u64, String> = HashMap::new();
+        let mut start_block = from_block;
+        let mut chunk_size = self.chunk_size;
+
+        while start_block <= to_block {
+            let end_block = (start_block + chunk_size - 1).min(to_block);
+
+            let filter = FilterBuilder::default()
+                .address(vec![address])
+                .topics(Some(vec![transfer_topic]), None, None, None)
+                .from_block(BlockNumber::Number(U64::from(start_block)))
+                .to_block(BlockNumber::Number(U64::from(end_block)))
+                .build();
+
+            let logs = match self.web3.eth().logs(filter).await {
+                Ok(x) => x,
+                Err(e) if chunk_size > 1 => {
+                    chunk_size /= 2;
+                    warn!("RpcTransferSource::transfer_rows|start_block={} chunk_size={} error={}", start_block, chunk_size, e);
+                    continue;
+                },
+                Err(e) => return Err(rpc_err(e.to_string())),
+            };
+
+            info!("RpcTransferSource::transfer_rows|blocks={}..={} logs={}", start_block, end_block, logs.len());
+
+            if logs.len() < RPC_SPARSE_LOGS {
+                chunk_size = (chunk_size * 2).min(RPC_MAX_CHUNK_SIZE);
+            }
+            start_block = end_block + 1;
+
+            // ERC20 Transfer shares the signature but does not index the value
+            for log in logs.iter().filter(|x| x.topics.len() == 4) {
+                let row = rows.len();
+                let malformed = |field| Error::MalformedTransfer { contract: String::from(contract), row, field };
+
+                let block = log.block_number.ok_or_else(|| malformed("blockNumber"))?.as_u64();
+                let block_hash = log.block_hash.ok_or_else(|| malformed("blockHash"))?;
+                let hash = log.transaction_hash.ok_or_else(|| malformed("hash"))?;
+                let txn_index = log.transaction_index.ok_or_else(|| malformed("transactionIndex"))?;
+
+                if let Entry::Vacant(entry) = timestamps.entry(block) {
+                    entry.insert(self.block_timestamp(block).await.map_err(|e| rpc_err(e.to_string()))?);
+                }
+
+                rows.push(json!({
+                    "blockNumber": block.to_string(),
+                    "timeStamp": timestamps[&block],
+                    "hash": format!("{:?}", hash),
+                    "nonce": "",
+                    "blockHash": format!("{:?}", block_hash),
+                    "from": format!("{:?}", Address::from_slice(&log.topics[1].as_bytes()[12..])),
+                    "contractAddress": format!("{:?}", log.address),
+                    "to": format!("{:?}", Address::from_slice(&log.topics[2].as_bytes()[12..])),
+                    "tokenID": U256::from_big_endian(log.topics[3].as_bytes()).to_string(),
+                    "tokenName": token_name,
+                    "tokenSymbol": token_symbol,
+                    "tokenDecimal": "0",
+                    "transactionIndex": txn_index.to_string(),
+                    "gas": "",
+                    "gasPrice": "",
+                    "gasUsed": "",
+                    "cumulativeGasUsed": "",
+                    "confirmations": (head - block + 1).to_string(),
+                }));
+            }
+        }
+
+        info!("RpcTransferSource::transfer_rows|completed");
+        Ok(rows)
+    }
+
+    async fn block_timestamp(&self, block: u64) -> web3::Result<String> {
+        let block = self.web3.eth()
+            .block(BlockId::Number(BlockNumber::Number(U64::from(block))))
+            .await?
+            .ok_or_else(|| web3::Error::InvalidResponse(format!("block {} not found", block)))?;
+
+        Ok(block.timestamp.to_string())
+    }
+
+    /// name() and symbol() of the contract, empty if it does not implement the metadata extension
+    async fn metadata(&self, address: Address) -> (String, String) {
+        let contract = match Contract::from_json(self.web3.eth(), address, ERC721_METADATA_ABI) {
+            Ok(x) => x,
+            Err(_) => return (String::new(), String::new()),
+        };
+
+        let name: String = contract.query("name", (), None, Options::default(), None).await.unwrap_or_default();
+        let symbol: String = contract.query("symbol", (), None, Options::default(), None).await.unwrap_or_default();
+        (name, symbol)
+    }
+}
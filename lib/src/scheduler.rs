@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::time::Instant;
+use log::{error, info, warn};
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use futures::FutureExt;
+
+use conf::{Config, JobConfig};
+
+use crate::{collection_publishers, erc721_mint_act, export_act, find_collection, ledgered, migration_act};
+use crate::{ChartOptions, Error, Outbox, Publisher, Result, Target, TransferSource};
+
+/// A job of the schedule section with its parsed cron expression
+pub struct Job {
+    pub name: String,
+    pub config: JobConfig,
+    schedule: Schedule,
+}
+
+impl Job {
+    pub fn from_config(config: &JobConfig) -> Result<Job> {
+        let schedule = config.schedule().map_err(|e| Error::InvalidConfig {
+            key: format!("schedule.{}.cron", config.name()),
+            reason: e.to_string(),
+        })?;
+
+        Ok(Job { name: config.name(), config: config.clone(), schedule })
+    }
+
+    /// First run strictly after the given time
+    pub fn next_run(&self, after: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.schedule.after(after).next()
+    }
+}
+
+/// Runs the jobs of the schedule section at their cron times, one after the other.
+/// A job failing or panicking is logged and the schedule carries on.
+pub struct Scheduler {
+    jobs: Vec<Job>,
+}
+
+impl Scheduler {
+    pub fn new(jobs: Vec<Job>) -> Scheduler {
+        Scheduler { jobs }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Scheduler> {
+        Ok(Scheduler::new(config.schedule.iter().map(Job::from_config).collect::<Result<_>>()?))
+    }
+
+    pub fn jobs(&self) -> &[Job] {
+        &self.jobs
+    }
+
+    /// Time of the next run after the given time and the jobs due then, None when no job runs again
+    pub fn next_due(&self, after: &DateTime<Utc>) -> Option<(DateTime<Utc>, Vec<&Job>)> {
+        let runs: Vec<(DateTime<Utc>, &Job)> = self.jobs.iter()
+            .filter_map(|job| job.next_run(after).map(|at| (at, job)))
+            .collect();
+        let at = runs.iter().map(|(at, _)| *at).min()?;
+
+        Some((at, runs.into_iter().filter(|(x, _)| *x == at).map(|(_, job)| job).collect()))
+    }
+
+    /// Sleeps until each next run and hands the due jobs to runner, until shutdown resolves.
+    /// A job is never started twice for the same time, runs missed while a job was busy are skipped.
+    pub async fn run<'s, F, Fut, S>(&'s self, mut runner: F, shutdown: S) -> Result<()>
+    where
+        F: FnMut(&'s Job) -> Fut,
+        Fut: Future<Output = Result<()>>,
+        S: Future<Output = ()>,
+    {
+        tokio::pin!(shutdown);
+        let mut last_run: Option<DateTime<Utc>> = None;
+
+        loop {
+            let after = Utc::now().max(last_run.unwrap_or_else(Utc::now));
+            let (at, due) = match self.next_due(&after) {
+                Some(x) => x,
+                None => {
+                    warn!("Scheduler::run|no job is due again, stopping");
+                    return Ok(());
+                },
+            };
+
+            let names: Vec<&str> = due.iter().map(|x| x.name.as_str()).collect();
+            info!("Scheduler::run|next run at {} jobs={:?}", at, names);
+
+            let wait = (at - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {},
+                _ = &mut shutdown => {
+                    info!("Scheduler::run|shutting down");
+                    return Ok(());
+                },
+            }
+            last_run = Some(at);
+
+            for job in due {
+                info!("Scheduler::run|job={} starting", job.name);
+                let started = Instant::now();
+
+                match AssertUnwindSafe(runner(job)).catch_unwind().await {
+                    Ok(Ok(())) => info!("Scheduler::run|job={} succeeded in {:.1}s", job.name, started.elapsed().as_secs_f64()),
+                    Ok(Err(e)) => error!("Scheduler::run|job={} failed in {:.1}s: {}", job.name, started.elapsed().as_secs_f64(), e),
+                    Err(panic) => {
+                        let reason = panic.downcast_ref::<String>().cloned()
+                            .or_else(|| panic.downcast_ref::<&str>().map(|x| String::from(*x)))
+                            .unwrap_or_default();
+                        error!("Scheduler::run|job={} panicked: {}", job.name, reason);
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Utility method to run a job of the schedule section once, as the command line would
+pub async fn run_job(config: &Config, source: &dyn TransferSource, job: &JobConfig) -> Result<()> {
+    let collection = find_collection(&config.collections, &job.collection)?;
+    let target = Target::Collection(collection);
+
+    let publishers: Vec<Box<dyn Publisher>> = match (job.dry_run, job.post) {
+        (true, _) => vec![Box::new(Outbox::from_config(&config.output))],
+        (false, true) => ledgered(config, collection_publishers(config, Some(collection))?, false)?,
+        (false, false) => vec![],
+    };
+
+    match job.command.as_str() {
+        "migration" => migration_act(config, source, collection, &publishers).await,
+        "mint-activity" => {
+            let options = ChartOptions::from_config(&config.charts)?;
+            erc721_mint_act(config, source, &options, &target, job.lookback_days.unwrap_or(0), None, &publishers).await?;
+            Ok(())
+        },
+        "export" => export_act(source, &config.output, &target, None, None).await,
+        command => Err(Error::InvalidConfig {
+            key: format!("schedule.{}.command", job.name()),
+            reason: format!("{} cannot be scheduled", command),
+        }),
+    }
+}
@@ -0,0 +1,212 @@
+//! HTTP api of the serve command, the numbers the bot posts as json:
+//!
+//! - `GET /health`
+//! - `GET /collections`: the configured collections
+//! - `GET /collections/{name}/mint-activity?lookback=30`: mint-activity variables and the daily mints
+//! - `GET /collections/{name}/migration`: migration variables
+//! - `GET /collections/{name}/holders?top=10`: holder count and the top holders
+//!
+//! Answers are read from the transfer cache as stored, requests never reach Etherscan. A
+//! background task syncs every collection into the cache when the server starts, then each
+//! server.refresh_mins; a collection is 404 until its first sync. Errors answer
+//! `{"error": "..."}`, 400 for a bad parameter, 404 for an unknown collection, 502 / 503 when
+//! a max supply cannot be read through rpc.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+use log::{info, warn};
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use polars::frame::DataFrame;
+use serde_json::{json, Value};
+use tokio::time::MissedTickBehavior;
+
+use conf::{Collection, Config};
+
+use crate::{find_collection, holders, migration_status, mint_activity_status};
+use crate::{Error, EtherscanClient, Result, Target, TransferCache, TransferSource};
+
+/// Holders listed by /holders when the request sets no top
+const DEFAULT_TOP: usize = 10;
+
+/// Minutes between cache syncs when server.refresh_mins is unset
+const DEFAULT_REFRESH_MINS: u64 = 10;
+
+struct ApiState {
+    config: Config,
+    cache: Arc<TransferCache>,
+}
+
+/// Error answered as {"error": ...} with a status fitting the Error
+struct ApiError(Error);
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        ApiError(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self.0 {
+            Error::InvalidArgument { name, .. } if name == "collection" => StatusCode::NOT_FOUND,
+            Error::InvalidArgument { .. } => StatusCode::BAD_REQUEST,
+            Error::NoTransfers { .. } => StatusCode::NOT_FOUND,
+            e if e.is_transient() => StatusCode::SERVICE_UNAVAILABLE,
+            Error::Etherscan { .. } | Error::Rpc { .. } | Error::Http(_) | Error::Json(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        warn!("ApiError|status={} {}", status, self.0);
+
+        (status, Json(json!({ "error": self.0.to_string() }))).into_response()
+    }
+}
+
+type ApiResult = std::result::Result<Json<Value>, ApiError>;
+
+/// Routes of the api over a config and the transfer cache, read without syncing
+pub fn router(config: Config, cache: Arc<TransferCache>) -> Router {
+    Router::new()
+        .route("/health", get(|| async { Json(json!({ "status": "ok" })) }))
+        .route("/collections", get(collections))
+        .route("/collections/:name/mint-activity", get(mint_activity))
+        .route("/collections/:name/migration", get(migration))
+        .route("/collections/:name/holders", get(holders_of))
+        .with_state(Arc::new(ApiState { config, cache }))
+}
+
+/// Utility method to serve the api on listener until shutdown resolves, syncing the cache
+/// of every collection from client in the background meanwhile
+pub async fn serve<S>(listener: TcpListener, config: Config, client: EtherscanClient, cache: TransferCache, shutdown: S) -> Result<()>
+where
+    S: Future<Output = ()>,
+{
+    info!("serve|addr={}", listener.local_addr()?);
+
+    let cache = Arc::new(cache);
+    let every = Duration::from_secs(60 * config.server.refresh_mins.unwrap_or(DEFAULT_REFRESH_MINS));
+    let refresh = tokio::spawn(refresh_cache(client, cache.clone(), config.collections.clone(), every));
+
+    let served = axum::Server::from_tcp(listener)
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?
+        .serve(router(config, cache).into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await
+        .map_err(|e| Error::Io(std::io::Error::other(e)));
+
+    refresh.abort();
+    served?;
+
+    info!("serve|completed");
+    Ok(())
+}
+
+/// Utility method to sync the cache of every collection right away, then each every, forever.
+/// A failed sync is logged and retried on the next tick, the stored rows stay as they were.
+pub async fn refresh_cache(client: EtherscanClient, cache: Arc<TransferCache>, collections: Vec<Collection>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        for collection in &collections {
+            match cache.sync(&client, &collection.address).await {
+                Ok(rows) => info!("refresh_cache|collection={} rows={}", collection.name, rows.len()),
+                Err(e) => warn!("refresh_cache|collection={} error={}", collection.name, e),
+            }
+        }
+    }
+}
+
+async fn collections(State(state): State<Arc<ApiState>>) -> Json<Value> {
+    let collections: Vec<Value> = state.config.collections.iter()
+        .map(|x| json!({ "name": x.name, "display_name": x.display_name, "address": x.address, "chain": x.chain }))
+        .collect();
+
+    Json(json!({ "collections": collections }))
+}
+
+async fn mint_activity(
+    State(state): State<Arc<ApiState>>,
+    Path(name): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult {
+
+    let lookback = param(&query, "lookback")?.unwrap_or(0);
+    info!("mint_activity|collection={} lookback={}", name, lookback);
+
+    let collection = find_collection(&state.config.collections, &name)?;
+    let (df, mut vars) = mint_activity_status(state.cache.as_ref(), &Target::Collection(collection), lookback as u64).await?;
+    vars["daily"] = daily_json(&df)?;
+
+    Ok(Json(vars))
+}
+
+async fn migration(State(state): State<Arc<ApiState>>, Path(name): Path<String>) -> ApiResult {
+    info!("migration|collection={}", name);
+
+    let collection = find_collection(&state.config.collections, &name)?;
+    Ok(Json(migration_status(&state.config, state.cache.as_ref(), collection).await?))
+}
+
+async fn holders_of(
+    State(state): State<Arc<ApiState>>,
+    Path(name): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> ApiResult {
+
+    let top = param(&query, "top")?.unwrap_or(DEFAULT_TOP);
+    info!("holders|collection={} top={}", name, top);
+
+    let collection = find_collection(&state.config.collections, &name)?;
+    let target = Target::Collection(collection);
+    let df = holders(&state.cache.fetch(target.address(), target.from_block(), None).await?)?;
+
+    let mut body = holders_json(&df, top)?;
+    body["name"] = json!(collection.name);
+    body["display_name"] = json!(collection.display_name);
+    Ok(Json(body))
+}
+
+/// Utility fn to read a numeric query parameter, None when absent
+fn param(query: &HashMap<String, String>, name: &str) -> Result<Option<usize>> {
+    query.get(name)
+        .map(|x| x.parse::<usize>().map_err(|e| Error::InvalidArgument {
+            name: String::from(name),
+            value: x.clone(),
+            reason: e.to_string(),
+        }))
+        .transpose()
+}
+
+/// Utility fn to sum up a holders DataFrame with its first top rows
+fn holders_json(df: &DataFrame, top: usize) -> Result<Value> {
+    let held: u32 = df.column("tokens")?.sum().unwrap_or(0);
+
+    let top_df = df.head(Some(top));
+    let top: Vec<Value> = top_df.column("holder")?.utf8()?.into_iter()
+        .zip(top_df.column("tokens")?.u32()?)
+        .map(|(holder, tokens)| json!({ "holder": holder, "tokens": tokens }))
+        .collect();
+
+    Ok(json!({ "holders": df.height(), "tokens": held, "top": top }))
+}
+
+/// Utility fn to list a daily_mints DataFrame as [{"date": "YYYY-MM-DD", "mints": n}]
+fn daily_json(df: &DataFrame) -> Result<Value> {
+    let dates = df.column("timestamp")?.date()?.strftime("%Y-%m-%d");
+    let mints = df.column("mint_sum")?.u32()?;
+
+    Ok(Value::Array((&dates).into_iter()
+        .zip(mints)
+        .map(|(date, mints)| json!({ "date": date, "mints": mints.unwrap_or(0) }))
+        .collect()))
+}
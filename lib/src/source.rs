@@ -0,0 +1,137 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use polars::frame::DataFrame;
+use polars::prelude::BooleanChunked;
+
+use crate::{read_transfers, transfers_df, Error, EtherscanClient, Result, RpcTransferSource, TransferCache};
+
+/// A provider of ERC721 transfer history. Every implementation returns the
+/// get_erc721_transfers DataFrame schema so analytics do not care where data comes from.
+/// RpcTransferSource does not fill every column of it, see its docs.
+#[async_trait]
+pub trait TransferSource: Send + Sync {
+    /// Transfers of a contract in [from_block, to_block], to_block = None for the chain head
+    async fn fetch(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<DataFrame>;
+}
+
+#[async_trait]
+impl TransferSource for EtherscanClient {
+    async fn fetch(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<DataFrame> {
+        let rows = self.erc721_transfer_history(contract, from_block, to_block).await?;
+        transfers_df(contract, &rows)
+    }
+}
+
+#[async_trait]
+impl TransferSource for RpcTransferSource {
+    async fn fetch(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<DataFrame> {
+        let rows = self.transfer_rows(contract, from_block, to_block).await?;
+        transfers_df(contract, &rows)
+    }
+}
+
+/// Etherscan backed source that syncs new blocks into a TransferCache and serves from it
+pub struct CachedTransferSource {
+    client: EtherscanClient,
+    cache: TransferCache,
+}
+
+impl CachedTransferSource {
+    pub fn new(client: EtherscanClient, cache: TransferCache) -> CachedTransferSource {
+        CachedTransferSource { client, cache }
+    }
+}
+
+#[async_trait]
+impl TransferSource for CachedTransferSource {
+    async fn fetch(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<DataFrame> {
+        let rows = self.cache.sync(&self.client, contract).await?;
+        transfers_df(contract, &in_range(contract, rows, from_block, to_block)?)
+    }
+}
+
+/// The rows stored in the cache as they are, without syncing. The serve command reads the
+/// cache this way while a background task keeps it synced.
+#[async_trait]
+impl TransferSource for TransferCache {
+    async fn fetch(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<DataFrame> {
+        transfers_df(contract, &in_range(contract, self.rows(contract)?, from_block, to_block)?)
+    }
+}
+
+/// Source reading a json array of Etherscan tokennfttx rows from disk, e.g. a test fixture
+pub struct FileTransferSource {
+    path: String,
+}
+
+impl FileTransferSource {
+    pub fn new(path: &str) -> FileTransferSource {
+        FileTransferSource { path: String::from(path) }
+    }
+}
+
+#[async_trait]
+impl TransferSource for FileTransferSource {
+    async fn fetch(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<DataFrame> {
+        let data = tokio::fs::read_to_string(&self.path).await?;
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&data)?;
+
+        let rows: Vec<serde_json::Value> = rows.into_iter()
+            .filter(|x| x["contractAddress"].as_str().is_some_and(|x| x.eq_ignore_ascii_case(contract)))
+            .collect();
+
+        transfers_df(contract, &in_range(contract, rows, from_block, to_block)?)
+    }
+}
+
+/// Source serving a transfer dataset written by the export command, loaded once on open
+pub struct DatasetTransferSource {
+    df: DataFrame,
+}
+
+impl DatasetTransferSource {
+    pub fn open(path: &str) -> Result<DatasetTransferSource> {
+        Ok(DatasetTransferSource { df: read_transfers(path)? })
+    }
+}
+
+#[async_trait]
+impl TransferSource for DatasetTransferSource {
+    async fn fetch(&self, contract: &str, from_block: u64, to_block: Option<u64>) -> Result<DataFrame> {
+        let mask: BooleanChunked = self.df.column("contract_address")?
+            .utf8()?
+            .into_iter()
+            .zip(self.df.column("block_num")?.u64()?)
+            .map(|(addr, block)| {
+                addr.is_some_and(|x| x.eq_ignore_ascii_case(contract))
+                    && block.is_some_and(|x| x >= from_block && to_block.is_none_or(|to| x <= to))
+            })
+            .collect();
+
+        let df = self.df.filter(&mask)?;
+        if df.height() == 0 { return Err(Error::NoTransfers { contract: String::from(contract) }); }
+        Ok(df)
+    }
+}
+
+/// Utility fn to keep the rows in [from_block, to_block]
+fn in_range(
+    contract: &str,
+    rows: Vec<serde_json::Value>,
+    from_block: u64,
+    to_block: Option<u64>,
+) -> Result<Vec<serde_json::Value>> {
+    let mut out = vec![];
+
+    for (i, row) in rows.into_iter().enumerate() {
+        let block = row["blockNumber"].as_str()
+            .and_then(|x| u64::from_str(x).ok())
+            .ok_or_else(|| Error::MalformedTransfer { contract: String::from(contract), row: i, field: "blockNumber" })?;
+
+        if block >= from_block && to_block.is_none_or(|x| block <= x) {
+            out.push(row);
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,156 @@
+//! Status templates. Every command posting a status renders it with handlebars from the
+//! templates of its collection, the templates section of the config or the defaults below.
+//!
+//! Variables of every command:
+//! - `name`, `display_name`: collection name and display name, the address and token name for --address
+//! - `total`: tokens minted in the window
+//! - `minted_today`: tokens minted on the last mint day, `last_day` (YYYY-MM-DD)
+//! - `days`: mint days in the window
+//! - `rank`: rank of the last mint day among the days of the window, 1 is the busiest
+//! - `deltas.day`: minted_today minus the mints of the mint day before
+//! - `deltas.week`: mints of the last 7 days minus those of the 7 days before
+//! - `pace`: average daily mints of the last 7 days
+//!
+//! mint-activity adds `title` and `lookback`. migration adds `max_supply`, `progress` (percent),
+//! `remaining`, `eta` (YYYY-MM-DD at the pace of the last 7 days, n/a without mints) and `eta_days`.
+//!
+//! Helpers: `{{fixed progress 2}}` rounds to 2 places, `{{signed deltas.day}}` prints +3 / -2.
+//! Unknown variables are an error rather than blank, so typos show up in render-template.
+
+use chrono::{Duration, NaiveDate};
+use handlebars::{handlebars_helper, no_escape, Handlebars};
+use polars::datatypes::DataType::Int32;
+use polars::frame::DataFrame;
+use serde_json::{json, Map, Value};
+
+use conf::{Collection, Config};
+
+use crate::{Error, Result};
+
+pub const MIGRATION_TEMPLATE: &str = "- {{display_name}} Migration -
+Progress: {{fixed progress 2}}%
+Migrated Today: {{minted_today}}
+Supply: {{total}}/{{max_supply}}
+
+Remaining: {{remaining}}";
+
+pub const MINT_ACTIVITY_TEMPLATE: &str = "- {{title}} -
+Minted: {{total}}
+Last Mint Day: {{last_day}} ({{minted_today}})";
+
+/// Days the eta pace and deltas.week are measured over
+const PACE_DAYS: i32 = 7;
+
+handlebars_helper!(fixed: |x: f64, places: u64| format!("{:.*}", places as usize, x));
+handlebars_helper!(signed: |x: i64| format!("{:+}", x));
+
+/// Template a command posts with when neither the collection nor the config sets one
+pub fn default_template(command: &str) -> Option<&'static str> {
+    match command {
+        "migration" => Some(MIGRATION_TEMPLATE),
+        "mint-activity" => Some(MINT_ACTIVITY_TEMPLATE),
+        _ => None,
+    }
+}
+
+/// Utility fn to pick the template of a command: the collection's, the templates section's, or the default
+pub fn status_template<'a>(config: &'a Config, collection: Option<&'a Collection>, command: &str) -> Result<&'a str> {
+    collection.and_then(|x| x.templates.get(command))
+        .or_else(|| config.templates.get(command))
+        .map(|x| x.as_str())
+        .or_else(|| default_template(command))
+        .ok_or_else(|| Error::InvalidArgument {
+            name: String::from("command"),
+            value: String::from(command),
+            reason: String::from("has no status template"),
+        })
+}
+
+/// Utility fn to render a status template, trailing whitespace trimmed
+pub fn render_status(command: &str, template: &str, vars: &Value) -> Result<String> {
+    let mut registry = Handlebars::new();
+    registry.set_strict_mode(true);
+    registry.register_escape_fn(no_escape);
+    registry.register_helper("fixed", Box::new(fixed));
+    registry.register_helper("signed", Box::new(signed));
+
+    match registry.render_template(template, vars) {
+        Ok(status) => Ok(String::from(status.trim_end())),
+        Err(e) => Err(Error::Template { command: String::from(command), message: e.to_string() }),
+    }
+}
+
+/// Utility fn to work out the variables every command shares from a daily_mints DataFrame
+pub fn mint_vars(df: &DataFrame) -> Result<Map<String, Value>> {
+    let days: Vec<i32> = df.column("timestamp")?
+        .cast(&Int32)?
+        .i32()?
+        .into_iter()
+        .map(|x| x.unwrap_or_default())
+        .collect();
+    let mints: Vec<i64> = df.column("mint_sum")?
+        .u32()?
+        .into_iter()
+        .map(|x| x.unwrap_or_default() as i64)
+        .collect();
+
+    let last_day = days.last().copied();
+    let minted_today = mints.last().copied().unwrap_or(0);
+    let minted_before = match mints.len() {
+        0 | 1 => 0,
+        n => mints[n - 2],
+    };
+
+    let mut vars = Map::new();
+    vars.insert(String::from("total"), json!(mints.iter().sum::<i64>()));
+    vars.insert(String::from("minted_today"), json!(minted_today));
+    vars.insert(String::from("last_day"), json!(last_day.and_then(date).map(|x| x.to_string()).unwrap_or_default()));
+    vars.insert(String::from("days"), json!(days.len()));
+    vars.insert(String::from("rank"), json!(1 + mints.iter().filter(|x| **x > minted_today).count()));
+    vars.insert(String::from("deltas"), json!({
+        "day": minted_today - minted_before,
+        "week": last_day.map_or(0, |x| window_sum(&days, &mints, x) - window_sum(&days, &mints, x - PACE_DAYS)),
+    }));
+    vars.insert(String::from("pace"), json!(last_day.map_or(0.0, |x| window_sum(&days, &mints, x) as f64 / PACE_DAYS as f64)));
+
+    Ok(vars)
+}
+
+/// Utility fn to add the supply variables of migration to mint_vars
+pub fn migration_vars(mut vars: Map<String, Value>, max_supply: u64) -> Map<String, Value> {
+    let total = vars["total"].as_i64().unwrap_or(0);
+    let remaining = max_supply as i64 - total;
+    let pace = vars["pace"].as_f64().unwrap_or(0.0);
+    let eta_days = match (remaining, pace > 0.0) {
+        (x, _) if x <= 0 => Some(0),
+        (x, true) => Some((x as f64 / pace).ceil() as i64),
+        (_, false) => None,
+    };
+    let last_day = vars["last_day"].as_str().and_then(|x| NaiveDate::parse_from_str(x, "%Y-%m-%d").ok());
+
+    vars.insert(String::from("max_supply"), json!(max_supply));
+    vars.insert(String::from("progress"), json!(match max_supply {
+        0 => 0.0,
+        x => total as f64 / x as f64 * 100.0,
+    }));
+    vars.insert(String::from("remaining"), json!(remaining));
+    vars.insert(String::from("eta"), json!(match (last_day, eta_days) {
+        (Some(day), Some(n)) => (day + Duration::days(n)).to_string(),
+        _ => String::from("n/a"),
+    }));
+    vars.insert(String::from("eta_days"), json!(eta_days));
+    vars
+}
+
+/// Mints of the PACE_DAYS days ending on last_day
+fn window_sum(days: &[i32], mints: &[i64], last_day: i32) -> i64 {
+    days.iter().zip(mints)
+        .filter(|(day, _)| **day > last_day - PACE_DAYS && **day <= last_day)
+        .map(|(_, mints)| mints)
+        .sum()
+}
+
+/// Date of a polars Date value, days since the unix epoch. None when out of chrono's range.
+fn date(days: i32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(1970, 1, 1)?.checked_add_signed(Duration::days(days as i64))
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use lib::{get_erc721_transfers, EtherscanClient, TransferCache};
+use mock::MockEtherscan;
+use common::{BEARS, BEARS_FIXTURE};
+
+fn fixture() -> Vec<serde_json::Value> {
+    serde_json::from_str(&std::fs::read_to_string(BEARS_FIXTURE).unwrap()).unwrap()
+}
+
+async fn bears_mock(head: u64) -> (MockEtherscan, EtherscanClient) {
+    let mock = MockEtherscan::start().await;
+    mock.set_transfers(BEARS, fixture());
+    mock.set_head(head);
+
+    let client = EtherscanClient::new("test").base_url(&mock.base_url());
+    (mock, client)
+}
+
+#[tokio::test]
+async fn sync_fetches_only_new_blocks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("transfers.db");
+
+    let (mock, client) = bears_mock(13996600).await;
+
+    let cache = TransferCache::open(path.to_str().unwrap()).unwrap();
+    let df = get_erc721_transfers(&client, Some(&cache), BEARS).await.unwrap();
+    assert_eq!(df.height(), 6);
+    assert_eq!(cache.checkpoint(BEARS).unwrap(), Some(13996537));
+    drop(cache);
+
+    mock.set_head(14003100);
+
+    let cache = TransferCache::open(path.to_str().unwrap()).unwrap();
+    let df = get_erc721_transfers(&client, Some(&cache), BEARS).await.unwrap();
+
+    assert_eq!(mock.last_query()["startblock"], "13996538");
+    assert_eq!(df.height(), 7);
+    assert_eq!(cache.checkpoint(BEARS).unwrap(), Some(14003037));
+}
+
+#[tokio::test]
+async fn sync_without_new_blocks_keeps_checkpoint() {
+    let (_mock, client) = bears_mock(14003100).await;
+    let cache = TransferCache::open(":memory:").unwrap();
+
+    let first = get_erc721_transfers(&client, Some(&cache), BEARS).await.unwrap();
+    let second = get_erc721_transfers(&client, Some(&cache), BEARS).await.unwrap();
+
+    assert!(first.frame_equal(&second));
+    assert_eq!(cache.checkpoint(BEARS).unwrap(), Some(14003037));
+}
+
+#[tokio::test]
+async fn sync_replaces_reorged_provisional_blocks() {
+    let (mock, client) = bears_mock(14003010).await;
+    let cache = TransferCache::open(":memory:").unwrap();
+
+    get_erc721_transfers(&client, Some(&cache), BEARS).await.unwrap();
+    assert_eq!(cache.checkpoint(BEARS).unwrap(), Some(14002947));
+
+    // block 14003000 is orphaned, its mint is re-included two blocks later
+    let mut rows = fixture();
+    let last = rows.len() - 1;
+    rows[last]["blockNumber"] = serde_json::json!("14003002");
+    rows[last]["blockHash"] = serde_json::json!("0xreorged");
+    mock.set_transfers(BEARS, rows);
+    mock.set_head(14003100);
+
+    let df = get_erc721_transfers(&client, Some(&cache), BEARS).await.unwrap();
+    let blocks: Vec<String> = cache.rows(BEARS).unwrap().iter()
+        .map(|x| String::from(x["blockNumber"].as_str().unwrap()))
+        .collect();
+
+    assert_eq!(mock.last_query()["startblock"], "14002948");
+    assert_eq!(df.height(), 7);
+    assert!(!blocks.contains(&String::from("14003000")));
+    assert!(blocks.contains(&String::from("14003002")));
+    assert_eq!(cache.checkpoint(BEARS).unwrap(), Some(14003037));
+}
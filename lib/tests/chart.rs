@@ -0,0 +1,65 @@
+mod common;
+
+use std::str::FromStr;
+
+use conf::{Config, OutputConfig};
+use lib::{erc721_mint_act, ChartFormat, ChartOptions, FileTransferSource, Outbox, Publisher, Target};
+use common::{BEARS, BEARS_FIXTURE};
+
+#[tokio::test]
+async fn mint_activity_chart_is_written_to_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = FileTransferSource::new(BEARS_FIXTURE);
+    let config = Config {
+        output: OutputConfig { dir: dir.path().to_string_lossy().into_owned(), ..OutputConfig::default() },
+        ..Config::default()
+    };
+    let options = ChartOptions::default().format(ChartFormat::Html);
+
+    let path = erc721_mint_act(&config, &source, &options, &Target::Address(BEARS), 0, None, &[]).await.unwrap();
+
+    assert_eq!(path, dir.path().join(format!("{}_mint_activity.html", BEARS.to_lowercase())));
+    let html = std::fs::read_to_string(&path).unwrap();
+    assert!(html.contains("Historical Mint Activity"));
+
+    // an explicit path picks the format from its extension
+    let requested = dir.path().join("bears.htm");
+    let options = ChartOptions::default();
+    let path = erc721_mint_act(&config, &source, &options, &Target::Address(BEARS), 2, Some(&requested.to_string_lossy()), &[]).await.unwrap();
+
+    assert_eq!(path, requested);
+    assert!(std::fs::read_to_string(&path).unwrap().contains("2D Mint Activity"));
+}
+
+#[tokio::test]
+async fn html_chart_is_written_at_the_configured_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = FileTransferSource::new(BEARS_FIXTURE);
+    let path = dir.path().join("bears.html");
+    let options = ChartOptions::default().width(640).height(360);
+
+    erc721_mint_act(&Config::default(), &source, &options, &Target::Address(BEARS), 0, Some(&path.to_string_lossy()), &[]).await.unwrap();
+
+    let html = std::fs::read_to_string(&path).unwrap();
+    assert!(html.contains(r#""width":640"#), "{}", html);
+    assert!(html.contains(r#""height":360"#), "{}", html);
+}
+
+#[test]
+fn chart_formats() {
+    assert_eq!(ChartFormat::from_path("/tmp/bears.jpg").unwrap(), ChartFormat::Jpeg);
+    assert_eq!(ChartFormat::from_str("SVG").unwrap(), ChartFormat::Svg);
+    assert!(ChartFormat::from_path("/tmp/bears.pdf").is_err());
+}
+
+#[tokio::test]
+async fn posted_charts_must_be_images() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = FileTransferSource::new(BEARS_FIXTURE);
+    let options = ChartOptions::default().format(ChartFormat::Html);
+    let publishers: Vec<Box<dyn Publisher>> = vec![Box::new(Outbox::new(&dir.path().to_string_lossy()))];
+
+    let err = erc721_mint_act(&Config::default(), &source, &options, &Target::Address(BEARS), 0, None, &publishers).await.unwrap_err();
+
+    assert!(err.to_string().contains("png or jpeg"), "{}", err);
+}
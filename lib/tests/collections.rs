@@ -0,0 +1,79 @@
+use std::net::TcpListener;
+
+use conf::{Config, MaxSupply, ReadFrom};
+use lib::{find_collection, get_max_supply, Error};
+use mock::MockRpc;
+
+const CONFIG: &str = r#"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+  - name: "bees"
+    display_name: "Bees Deluxe"
+    address: "0x1c2CD50f9Efb463bDd2ec9E36772c14A8D1658B3"
+    max_supply: "contract"
+rpc:
+  url: "http://127.0.0.1:8545"
+"#;
+
+#[test]
+fn collections_are_found_by_name() {
+    let config = Config::from_yaml(CONFIG).unwrap();
+
+    let bears = find_collection(&config.collections, "bears").unwrap();
+    assert_eq!(bears.display_name, "Bears Deluxe");
+    assert_eq!(bears.chain, "ethereum");
+    assert_eq!(bears.max_supply, MaxSupply::Fixed(6900));
+
+    let bees = find_collection(&config.collections, "Bees").unwrap();
+    assert_eq!(bees.max_supply, MaxSupply::Read(ReadFrom::Contract));
+}
+
+#[test]
+fn unknown_collection_is_reported() {
+    let config = Config::from_yaml(CONFIG).unwrap();
+
+    assert!(matches!(find_collection(&config.collections, "wasps"), Err(Error::InvalidArgument { .. })));
+}
+
+#[tokio::test]
+async fn fixed_supply_needs_no_rpc() {
+    let config = Config::from_yaml(CONFIG).unwrap();
+    let bears = find_collection(&config.collections, "bears").unwrap();
+
+    let supply = get_max_supply(None, bears).await.unwrap();
+
+    assert_eq!(supply.as_u64(), 6900);
+}
+
+#[tokio::test]
+async fn unreadable_supply_is_not_retried() {
+    let mock = MockRpc::start().await;
+    let yaml = CONFIG.replace("http://127.0.0.1:8545", &mock.url());
+    let mut config = Config::from_yaml(&yaml).unwrap();
+
+    // the mock reverts every eth_call it has no metadata for, as a contract without MAX_SUPPLY does
+    let bees = find_collection(&config.collections, "bees").unwrap();
+    let e = get_max_supply(config.rpc.as_ref(), bees).await.unwrap_err();
+    assert!(matches!(&e, Error::InvalidConfig { key, .. } if key == "collections.bees.max_supply"), "{}", e);
+    assert!(!e.is_transient());
+
+    config.collections[1].address = String::from("0xnot-an-address");
+    let e = get_max_supply(config.rpc.as_ref(), &config.collections[1]).await.unwrap_err();
+    assert!(matches!(&e, Error::InvalidConfig { key, .. } if key == "collections.bees.address"), "{}", e);
+    assert!(!e.is_transient());
+}
+
+#[tokio::test]
+async fn unreachable_node_is_retried() {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let yaml = CONFIG.replace("http://127.0.0.1:8545", &format!("http://127.0.0.1:{}", port));
+    let config = Config::from_yaml(&yaml).unwrap();
+
+    let bees = find_collection(&config.collections, "bees").unwrap();
+    let e = get_max_supply(config.rpc.as_ref(), bees).await.unwrap_err();
+    assert!(matches!(e, Error::Rpc { .. }), "{}", e);
+    assert!(e.is_transient());
+}
@@ -0,0 +1,15 @@
+//! Fixtures and helpers shared by the lib integration tests. Each test crate uses a subset.
+#![allow(dead_code)]
+
+use polars::frame::DataFrame;
+
+/// Bears Deluxe contract, the address of the rows in BEARS_FIXTURE
+pub const BEARS: &str = "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B";
+
+/// Etherscan tokennfttx rows of Bears Deluxe, 6 mints over 3 days
+pub const BEARS_FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/bears_transfers.json");
+
+/// mint_sum column of a daily_mints DataFrame
+pub fn mint_sums(df: &DataFrame) -> Vec<u32> {
+    df.column("mint_sum").unwrap().u32().unwrap().into_iter().map(|x| x.unwrap()).collect()
+}
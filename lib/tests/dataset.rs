@@ -0,0 +1,71 @@
+mod common;
+
+use lib::{daily_mints, mint_act, read_df, write_df, DataFormat, DatasetTransferSource, Error, FileTransferSource, TransferSource};
+use common::{BEARS, BEARS_FIXTURE, mint_sums};
+
+async fn round_trip(file_name: &str) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(file_name);
+    let path = path.to_str().unwrap();
+
+    let fixture = FileTransferSource::new(BEARS_FIXTURE);
+    let mut df = fixture.fetch(BEARS, 0, None).await.unwrap();
+    write_df(&mut df, path, DataFormat::from_path(path).unwrap()).unwrap();
+
+    let dataset = DatasetTransferSource::open(path).unwrap();
+    let imported = dataset.fetch(BEARS, 0, None).await.unwrap();
+
+    assert!(imported.frame_equal_missing(&df));
+    assert_eq!(mint_sums(&mint_act(&dataset, BEARS).await.unwrap()), vec![2, 3, 1]);
+    assert_eq!(dataset.fetch(BEARS, 13996500, Some(13996530)).await.unwrap().height(), 4);
+}
+
+#[tokio::test]
+async fn csv_round_trip_keeps_transfer_schema() {
+    round_trip("bears.csv").await;
+}
+
+#[tokio::test]
+async fn json_round_trip_keeps_transfer_schema() {
+    round_trip("bears.json").await;
+}
+
+#[tokio::test]
+async fn parquet_round_trip_keeps_transfer_schema() {
+    round_trip("bears.parquet").await;
+}
+
+#[tokio::test]
+async fn stats_are_exported() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("mint_act.csv");
+    let path = path.to_str().unwrap();
+
+    let df = FileTransferSource::new(BEARS_FIXTURE).fetch(BEARS, 0, None).await.unwrap();
+    write_df(&mut daily_mints(df).unwrap(), path, DataFormat::Csv).unwrap();
+
+    let stats = read_df(path, DataFormat::Csv).unwrap();
+    assert_eq!(stats.get_column_names(), vec!["timestamp", "token_name", "mint_sum"]);
+    assert_eq!(stats.height(), 3);
+}
+
+#[tokio::test]
+async fn dataset_reports_unknown_contract() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bears.json");
+    let path = path.to_str().unwrap();
+
+    let mut df = FileTransferSource::new(BEARS_FIXTURE).fetch(BEARS, 0, None).await.unwrap();
+    write_df(&mut df, path, DataFormat::Json).unwrap();
+
+    let err = DatasetTransferSource::open(path).unwrap()
+        .fetch("0x0000000000000000000000000000000000000001", 0, None).await.unwrap_err();
+    assert!(matches!(err, Error::NoTransfers { .. }));
+}
+
+#[test]
+fn formats_follow_the_extension() {
+    assert_eq!(DataFormat::from_path("bears.parquet").unwrap(), DataFormat::Parquet);
+    assert_eq!(DataFormat::from_path("bears.ndjson").unwrap(), DataFormat::Json);
+    assert!(matches!(DataFormat::from_path("bears.xlsx"), Err(Error::InvalidArgument { .. })));
+}
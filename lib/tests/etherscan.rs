@@ -0,0 +1,185 @@
+mod common;
+
+use lib::{mint_act, Error, EtherscanClient, TransferCache};
+use mock::MockEtherscan;
+use common::{BEARS, BEARS_FIXTURE, mint_sums};
+
+async fn bears_mock() -> (MockEtherscan, EtherscanClient) {
+    let mock = MockEtherscan::start().await;
+    mock.load_fixture(BEARS, BEARS_FIXTURE);
+
+    let client = EtherscanClient::new("test").base_url(&mock.base_url());
+    (mock, client)
+}
+
+#[tokio::test]
+async fn mint_act_sums_daily_mints() {
+    let (_mock, client) = bears_mock().await;
+
+    let df = mint_act(&client, BEARS).await.unwrap();
+
+    assert_eq!(mint_sums(&df), vec![2, 3, 1]);
+}
+
+#[tokio::test]
+async fn mint_act_retries_rate_limit() {
+    let (mock, client) = bears_mock().await;
+    mock.push_response(200, r#"{"status":"0","message":"NOTOK","result":"Max rate limit reached"}"#);
+
+    let df = mint_act(&client, BEARS).await.unwrap();
+
+    assert_eq!(mint_sums(&df), vec![2, 3, 1]);
+    assert!(mock.calls() > 1);
+}
+
+#[tokio::test]
+async fn mint_act_retries_too_many_requests() {
+    let (mock, client) = bears_mock().await;
+    mock.push_response(429, "Too Many Requests");
+
+    let df = mint_act(&client, BEARS).await.unwrap();
+
+    assert_eq!(mint_sums(&df), vec![2, 3, 1]);
+    assert!(mock.calls() > 1);
+}
+
+#[tokio::test]
+async fn http_errors_leave_out_the_api_key() {
+    let mock = MockEtherscan::start().await;
+    mock.push_response(500, "Internal Server Error");
+    let client = EtherscanClient::new("secret-api-key").base_url(&mock.base_url()).max_retries(0);
+
+    let err = client.erc721_transfer_history(BEARS, 0, None).await.unwrap_err();
+    assert!(matches!(err, Error::Http(_)), "{}", err);
+    assert!(!err.to_string().contains("secret-api-key"), "{}", err);
+
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let client = EtherscanClient::new("secret-api-key").base_url(&format!("http://{}/api", closed)).max_retries(0);
+
+    let err = client.erc721_transfer_history(BEARS, 0, None).await.unwrap_err();
+    assert!(matches!(err, Error::Http(_)), "{}", err);
+    assert!(!err.to_string().contains("secret-api-key"), "{}", err);
+}
+
+#[tokio::test]
+async fn mint_act_reports_unknown_contract() {
+    let (_mock, client) = bears_mock().await;
+
+    let err = mint_act(&client, "0x0000000000000000000000000000000000000001").await.unwrap_err();
+
+    assert!(matches!(err, Error::NoTransfers { .. }));
+}
+
+const MOCK: &str = "0x00000000000000000000000000000000000000aa";
+const ZERO: &str = "0x0000000000000000000000000000000000000000";
+const HOLDER: &str = "0x8ba1f109551bd432803012645ac136ddd64dba72";
+
+/// Builds `count` rows per entry of (block, count), each with a unique hash
+fn history(blocks: &[(u64, usize)]) -> Vec<serde_json::Value> {
+    let mut rows = vec![];
+    for (block, count) in blocks {
+        for _ in 0..*count {
+            let hash = format!("0x{:064x}", rows.len());
+            rows.push(mock::transfer_row(MOCK, *block, 1_640_000_000 + block, &hash, ZERO, HOLDER, rows.len() as u32));
+        }
+    }
+    rows
+}
+
+async fn paged_history(blocks: &[(u64, usize)]) -> (usize, lib::Result<Vec<serde_json::Value>>) {
+    let rows = history(blocks);
+    let expected = rows.len();
+
+    let mock = MockEtherscan::start().await;
+    mock.set_transfers(MOCK, rows);
+
+    let client = EtherscanClient::new("test").base_url(&mock.base_url()).calls_per_sec(1000);
+    (expected, client.erc721_transfer_history(MOCK, 0, None).await)
+}
+
+fn assert_exactly_once(expected: usize, rows: &[serde_json::Value]) {
+    let hashes: std::collections::HashSet<&str> = rows.iter().map(|x| x["hash"].as_str().unwrap()).collect();
+    assert_eq!(rows.len(), expected);
+    assert_eq!(hashes.len(), expected);
+}
+
+#[tokio::test]
+async fn history_is_empty_for_unknown_contract() {
+    let (_, rows) = paged_history(&[]).await;
+
+    assert!(rows.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn history_crosses_the_10k_window() {
+    let blocks: Vec<(u64, usize)> = (0..2_501).map(|x| (1_000 + x, 4)).collect();
+    let (expected, rows) = paged_history(&blocks).await;
+
+    assert_eq!(expected, 10_004);
+    assert_exactly_once(expected, &rows.unwrap());
+}
+
+#[tokio::test]
+async fn history_at_exactly_10k_rows() {
+    let blocks: Vec<(u64, usize)> = (0..2_500).map(|x| (1_000 + x, 4)).collect();
+    let (expected, rows) = paged_history(&blocks).await;
+
+    assert_eq!(expected, 10_000);
+    assert_exactly_once(expected, &rows.unwrap());
+}
+
+#[tokio::test]
+async fn history_keeps_dense_block_split_by_window() {
+    let mut blocks: Vec<(u64, usize)> = (0..9_000).map(|x| (1_000 + x, 1)).collect();
+    blocks.push((20_000, 2_500));
+    blocks.extend((0..500).map(|x| (20_001 + x, 1)));
+    let (expected, rows) = paged_history(&blocks).await;
+
+    assert_exactly_once(expected, &rows.unwrap());
+}
+
+#[tokio::test]
+async fn history_reads_block_larger_than_window() {
+    let (expected, rows) = paged_history(&[(900, 3), (1_000, 10_001), (1_001, 2)]).await;
+
+    assert_exactly_once(expected, &rows.unwrap());
+}
+
+#[tokio::test]
+async fn history_keeps_transfers_repeated_in_a_dense_block() {
+    // one transaction moves token 7 A -> B, B -> A, A -> B in the middle of a 10,001 row block
+    let mut rows = history(&[(1_000, 10_001)]);
+    for (i, (from, to)) in [(ZERO, HOLDER), (HOLDER, ZERO), (ZERO, HOLDER)].iter().enumerate() {
+        rows[5_000 + i] = mock::transfer_row(MOCK, 1_000, 1_640_001_000, &format!("0x{:064x}", 7), from, to, 7);
+    }
+    let expected = rows.clone();
+
+    let mock = MockEtherscan::start().await;
+    mock.set_transfers(MOCK, rows);
+
+    let client = EtherscanClient::new("test").base_url(&mock.base_url()).calls_per_sec(1000);
+    let history = client.erc721_transfer_history(MOCK, 0, None).await.unwrap();
+
+    assert_eq!(history, expected);
+}
+
+#[tokio::test]
+async fn sync_fails_on_a_block_beyond_two_windows() {
+    let mock = MockEtherscan::start().await;
+    mock.set_transfers(MOCK, history(&[(900, 3)]));
+    mock.set_head(990);
+
+    let client = EtherscanClient::new("test").base_url(&mock.base_url()).calls_per_sec(1000);
+    let cache = TransferCache::open(":memory:").unwrap();
+    cache.sync(&client, MOCK).await.unwrap();
+    let checkpoint = cache.checkpoint(MOCK).unwrap();
+    assert!(checkpoint.is_some());
+
+    mock.set_transfers(MOCK, history(&[(900, 3), (1_000, 20_001), (1_001, 2)]));
+    mock.set_head(2_000);
+    let err = cache.sync(&client, MOCK).await.unwrap_err();
+
+    assert!(matches!(err, Error::DenseBlock { block: 1_000, .. }), "{}", err);
+    assert_eq!(cache.checkpoint(MOCK).unwrap(), checkpoint);
+    assert_eq!(cache.rows(MOCK).unwrap().len(), 3);
+}
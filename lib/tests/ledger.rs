@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::json;
+
+use lib::{Ledger, LedgeredPublisher, Post, Publisher, WebhookPublisher};
+use mock::MockWebhook;
+
+fn post(minted_today: u32) -> Post {
+    Post::new("bears_migration", format!("- Bears Deluxe Migration -\nMigrated Today: {}", minted_today))
+        .collection("bears")
+        .metrics(json!({ "minted_today": minted_today, "total": 6 }))
+}
+
+#[tokio::test]
+async fn unchanged_posts_are_skipped_and_recorded_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("posts.db");
+    let server = MockWebhook::start().await;
+    server.push_response(200, r#"{"id": 42}"#);
+    server.push_response(200, r#"{"id": "43"}"#);
+
+    let ledger = Arc::new(Ledger::open(&path.to_string_lossy()).unwrap());
+    let publisher = LedgeredPublisher::new(Box::new(WebhookPublisher::new(&server.url("/hook"))), ledger.clone());
+
+    assert_eq!(publisher.publish(&post(3)).await.unwrap().as_deref(), Some("42"));
+    assert_eq!(publisher.publish(&post(3)).await.unwrap(), None);
+    assert_eq!(publisher.publish(&post(4)).await.unwrap().as_deref(), Some("43"));
+    assert_eq!(server.requests().len(), 2);
+
+    let history = ledger.history(Some("bears"), 10).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].status, post(4).status);
+    assert_eq!(history[0].metrics["minted_today"], 4);
+    assert_eq!(history[0].publisher, "webhook");
+    assert_eq!(history[1].remote_id.as_deref(), Some("42"));
+    assert!(ledger.history(Some("bees"), 10).unwrap().is_empty());
+
+    // reopening keeps the record
+    let ledger = Ledger::open(&path.to_string_lossy()).unwrap();
+    assert!(ledger.skip_reason(&post(4), "webhook").unwrap().unwrap().starts_with("no change"));
+    assert_eq!(ledger.skip_reason(&post(4), "discord").unwrap(), None);
+}
+
+#[tokio::test]
+async fn posts_wait_for_the_interval_of_their_publisher() {
+    let dir = tempfile::tempdir().unwrap();
+    let ledger = Ledger::open(&dir.path().join("posts.db").to_string_lossy()).unwrap()
+        .min_interval(Duration::from_secs(3600))
+        .interval("discord", Duration::ZERO);
+
+    ledger.record(&post(3), "twitter", Some("1")).unwrap();
+    ledger.record(&post(3), "discord", None).unwrap();
+
+    let reason = ledger.skip_reason(&post(4), "twitter").unwrap().unwrap();
+    assert!(reason.contains("next one is due"), "{}", reason);
+    assert_eq!(ledger.skip_reason(&post(4), "discord").unwrap(), None);
+
+    let ledger = ledger.force(true);
+    assert_eq!(ledger.skip_reason(&post(3), "twitter").unwrap(), None);
+}
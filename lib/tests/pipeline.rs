@@ -0,0 +1,91 @@
+mod common;
+
+use conf::Config;
+use lib::{run_pipeline, Data, Error, FileTransferSource, Outbox, Publisher};
+use common::BEARS_FIXTURE;
+
+fn config(dir: &str) -> Config {
+    Config::from_yaml(&format!(r#"
+output:
+  dir: "{}"
+charts:
+  format: "html"
+pipelines:
+  - name: "daily"
+    collection: "bears"
+    stages:
+      - id: "post"
+        stage: "publish"
+        inputs: ["status"]
+      - id: "status"
+        stage: "render"
+        inputs: ["supply"]
+        template: "{{{{display_name}}}} {{{{fixed progress 1}}}}% migrated, {{{{remaining}}}} to go"
+      - id: "supply"
+        stage: "metrics"
+        command: "migration"
+        inputs: ["mints"]
+      - id: "mints"
+        stage: "normalize"
+        inputs: ["transfers"]
+      - id: "transfers"
+        stage: "ingest"
+      - id: "owners"
+        stage: "holders"
+        inputs: ["transfers"]
+      - id: "owners_csv"
+        stage: "export"
+        inputs: ["owners"]
+        format: "csv"
+      - id: "activity"
+        stage: "chart"
+        inputs: ["mints"]
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+"#, dir)).unwrap()
+}
+
+#[tokio::test]
+async fn stages_run_after_their_inputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir.path().to_string_lossy());
+    let source = FileTransferSource::new(BEARS_FIXTURE);
+    let outbox = dir.path().join("outbox");
+    let publishers: Vec<Box<dyn Publisher>> = vec![Box::new(Outbox::new(&outbox.to_string_lossy()))];
+
+    let outputs = run_pipeline(&config, &source, &config.pipelines[0], &publishers).await.unwrap();
+
+    let kinds: Vec<(&str, &str)> = outputs.iter().map(|(id, data)| (id.as_str(), data.kind())).collect();
+    assert_eq!(kinds, vec![
+        ("activity", "file"), ("mints", "daily_mints"), ("owners", "holders"), ("owners_csv", "file"),
+        ("post", "post"), ("status", "status"), ("supply", "metrics"), ("transfers", "transfers"),
+    ]);
+
+    match &outputs["status"] {
+        Data::Status { command, status, .. } => {
+            assert_eq!(command, "migration");
+            assert_eq!(status, "Bears Deluxe 0.1% migrated, 6894 to go");
+        },
+        other => panic!("{:?}", other),
+    }
+    assert!(dir.path().join("bears_owners_csv.csv").exists());
+    assert!(dir.path().join("bears_activity.html").exists());
+
+    let entries: Vec<_> = std::fs::read_dir(&outbox).unwrap().map(|x| x.unwrap().path()).collect();
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].to_string_lossy().ends_with("bears_daily"));
+}
+
+#[tokio::test]
+async fn stage_failures_name_the_stage() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(&dir.path().to_string_lossy());
+    let source = FileTransferSource::new("/no/such/transfers.json");
+
+    let err = run_pipeline(&config, &source, &config.pipelines[0], &[]).await.unwrap_err();
+
+    assert!(matches!(&err, Error::Stage { stage, .. } if stage == "transfers"), "{}", err);
+}
@@ -0,0 +1,131 @@
+use conf::Config;
+use lib::{collection_publishers, publish_all, tweet_length, DiscordPublisher, Outbox, Post, Publisher, TelegramPublisher, WebhookPublisher, TWEET_MAX_LENGTH};
+use mock::MockWebhook;
+
+#[test]
+fn tweet_length_weighs_links_and_wide_characters() {
+    assert_eq!(tweet_length("Supply: 6900/6900"), 17);
+    assert_eq!(tweet_length("chart https://example.com/a/very/long/path/to/the/chart.png"), 6 + 23);
+    assert_eq!(tweet_length("熊 🐻"), 2 + 1 + 2);
+}
+
+#[tokio::test]
+async fn dry_run_writes_post_to_outbox() {
+    let dir = tempfile::tempdir().unwrap();
+    let chart = dir.path().join("bears_mint_activity.png");
+    std::fs::write(&chart, b"png").unwrap();
+
+    let outbox = dir.path().join("outbox");
+    let publisher = Outbox::new(&outbox.to_string_lossy());
+
+    let short = Post::new("bears_mint_activity", String::from("- Bears Deluxe 7D Mint Activity -")).attach(&chart);
+    let long = Post::new("bears_migration", "x".repeat(TWEET_MAX_LENGTH + 1));
+    publisher.publish(&short).await.unwrap();
+    publisher.publish(&long).await.unwrap();
+
+    let mut entries: Vec<_> = std::fs::read_dir(&outbox).unwrap().map(|x| x.unwrap().path()).collect();
+    entries.sort_by_key(|x| x.file_name().unwrap().to_string_lossy().ends_with("bears_migration"));
+    assert_eq!(entries.len(), 2);
+
+    let summary: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(entries[0].join("post.json")).unwrap()).unwrap();
+    assert_eq!(summary["length"], 33);
+    assert_eq!(summary["fits"], true);
+    assert_eq!(summary["attachments"][0], "bears_mint_activity.png");
+    assert_eq!(std::fs::read(entries[0].join("bears_mint_activity.png")).unwrap(), b"png");
+    assert_eq!(std::fs::read_to_string(entries[0].join("status.txt")).unwrap(), short.status);
+
+    let summary: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(entries[1].join("post.json")).unwrap()).unwrap();
+    assert_eq!(summary["fits"], false);
+}
+
+#[tokio::test]
+async fn chat_publishers_post_status_and_chart() {
+    let dir = tempfile::tempdir().unwrap();
+    let chart = dir.path().join("bears_mint_activity.png");
+    std::fs::write(&chart, b"png").unwrap();
+    let post = Post::new("bears_mint_activity", String::from("- Bears Deluxe 7D Mint Activity -"));
+
+    let server = MockWebhook::start().await;
+    let publishers: Vec<Box<dyn Publisher>> = vec![
+        Box::new(DiscordPublisher::new(&server.url("/api/webhooks/1/abc")).username(Some("Deluxe Stats"))),
+        Box::new(TelegramPublisher::new("123:abc", "@bears").api_url(&server.url("/"))),
+    ];
+
+    publish_all(&publishers, &post).await.unwrap();
+    publish_all(&publishers, &post.clone().attach(&chart)).await.unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 4);
+
+    assert_eq!(requests[0].path, "/api/webhooks/1/abc?wait=true");
+    assert_eq!(requests[0].json()["content"], post.status);
+    assert_eq!(requests[0].json()["username"], "Deluxe Stats");
+    assert_eq!(requests[1].path, "/bot123:abc/sendMessage");
+    assert_eq!(requests[1].json()["chat_id"], "@bears");
+    assert_eq!(requests[1].json()["text"], post.status);
+
+    assert!(requests[2].header("content-type").unwrap().starts_with("multipart/form-data"));
+    assert!(requests[2].text().contains(r#"name="files[0]"; filename="bears_mint_activity.png""#));
+    assert!(requests[2].text().contains(r#""content":"- Bears Deluxe 7D Mint Activity -""#));
+    assert_eq!(requests[3].path, "/bot123:abc/sendPhoto");
+    assert!(requests[3].text().contains(r#"name="caption""#));
+    assert!(requests[3].text().contains(r#"name="photo"; filename="bears_mint_activity.png""#));
+}
+
+#[tokio::test]
+async fn failing_publisher_does_not_stop_the_others() {
+    let dir = tempfile::tempdir().unwrap();
+    let chart = dir.path().join("bears_mint_activity.png");
+    std::fs::write(&chart, b"png").unwrap();
+    let post = Post::new("bears_migration", String::from("- Bears Deluxe Migration -")).attach(&chart);
+
+    let server = MockWebhook::start().await;
+    server.push_response(401, r#"{"message": "401: Unauthorized"}"#);
+    let publishers: Vec<Box<dyn Publisher>> = vec![
+        Box::new(DiscordPublisher::new(&server.url("/api/webhooks/1/revoked")).named("alerts")),
+        Box::new(WebhookPublisher::new(&server.url("/hook")).header("Authorization", "Bearer secret")),
+    ];
+
+    let err = publish_all(&publishers, &post).await.unwrap_err().to_string();
+    assert!(err.contains("alerts") && err.contains("401"), "{}", err);
+    assert!(!err.contains("webhook"), "{}", err);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].header("authorization"), Some("Bearer secret"));
+
+    let body = requests[1].json();
+    assert_eq!(body["name"], "bears_migration");
+    assert_eq!(body["length"], 26);
+    assert_eq!(body["attachments"][0]["file_name"], "bears_mint_activity.png");
+    assert_eq!(body["attachments"][0]["data"], "cG5n");
+}
+
+#[test]
+fn collections_publish_to_their_publishers() {
+    let config = Config::from_yaml(r#"
+twitter:
+  consumer_key: a
+  consumer_secret: b
+  access_key: c
+  access_secret: d
+publishers:
+  tweets:
+    type: twitter
+  alerts:
+    type: discord
+    webhook_url: https://discord.com/api/webhooks/1/abc
+collections:
+  - name: bears
+    display_name: Bears Deluxe
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+    publish: [tweets, alerts]
+"#).unwrap();
+
+    let names = |x: Vec<Box<dyn Publisher>>| x.iter().map(|x| String::from(x.name())).collect::<Vec<_>>();
+
+    assert_eq!(names(collection_publishers(&config, Some(&config.collections[0])).unwrap()), ["tweets", "alerts"]);
+    assert_eq!(names(collection_publishers(&config, None).unwrap()), ["twitter"]);
+    assert!(collection_publishers(&Config::default(), None).is_err());
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use lib::{mint_act, FileTransferSource, RpcTransferSource, TransferSource};
+use mock::{erc20_transfer_log, transfer_log, MockRpc};
+use common::{BEARS, BEARS_FIXTURE, mint_sums};
+
+const MINTER: &str = "0x8ba1f109551bd432803012645ac136ddd64dba72";
+const ZERO: &str = "0x0000000000000000000000000000000000000000";
+
+#[tokio::test]
+async fn transfer_rows_split_ranges_the_node_rejects() {
+    let mock = MockRpc::start().await;
+    for block in 1..=300u64 {
+        mock.add_log(transfer_log(BEARS, block, &format!("0x{:064x}", block), ZERO, MINTER, block), 1641988800 + block);
+    }
+    mock.set_max_range(100);
+
+    let source = RpcTransferSource::new(&mock.url()).unwrap().chunk_size(1_000);
+    let rows = source.transfer_rows(BEARS, 0, None).await.unwrap();
+
+    let token_ids: BTreeSet<String> = rows.iter().map(|x| String::from(x["tokenID"].as_str().unwrap())).collect();
+    assert_eq!(rows.len(), 300);
+    assert_eq!(token_ids.len(), 300);
+
+    let ranges = mock.log_ranges();
+    assert_eq!(ranges[0], (0, 300));
+    // the ranges the node answered tile [0, 300] without gaps or overlaps
+    let served: Vec<(u64, u64)> = ranges.into_iter().filter(|(from, to)| to - from < 100).collect();
+    assert_eq!(served.first().unwrap().0, 0);
+    assert_eq!(served.last().unwrap().1, 300);
+    assert!(served.windows(2).all(|x| x[1].0 == x[0].1 + 1));
+}
+
+#[tokio::test]
+async fn transfer_rows_skip_erc20_transfers() {
+    let mock = MockRpc::start().await;
+    mock.add_log(transfer_log(BEARS, 10, &format!("0x{:064x}", 1), ZERO, MINTER, 7), 1641988800);
+    mock.add_log(erc20_transfer_log(BEARS, 10, &format!("0x{:064x}", 2), MINTER, ZERO, 1_000_000), 1641988800);
+
+    let source = RpcTransferSource::new(&mock.url()).unwrap();
+    let rows = source.transfer_rows(BEARS, 0, None).await.unwrap();
+
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["tokenID"], "7");
+    assert_eq!(rows[0]["to"], MINTER);
+    assert_eq!(rows[0]["timeStamp"], "1641988800");
+}
+
+#[tokio::test]
+async fn rpc_source_agrees_with_etherscan_fixture() {
+    let mock = MockRpc::start().await;
+    mock.load_fixture(BEARS, BEARS_FIXTURE);
+
+    let rpc = RpcTransferSource::new(&mock.url()).unwrap();
+    let file = FileTransferSource::new(BEARS_FIXTURE);
+
+    let from_rpc = mint_act(&rpc, BEARS).await.unwrap();
+    let from_file = mint_act(&file, BEARS).await.unwrap();
+
+    assert_eq!(mint_sums(&from_rpc), vec![2, 3, 1]);
+    assert!(from_rpc.frame_equal(&from_file));
+
+    let df = rpc.fetch(BEARS, 0, None).await.unwrap();
+    assert_eq!(df.column("token_name").unwrap().utf8().unwrap().into_iter().next().flatten(), Some("Bears Deluxe"));
+    assert_eq!(df.column("token_symbol").unwrap().utf8().unwrap().into_iter().next().flatten(), Some("BEARS"));
+}
+
+#[tokio::test]
+async fn rpc_source_leaves_gas_columns_empty() {
+    let mock = MockRpc::start().await;
+    mock.load_fixture(BEARS, BEARS_FIXTURE);
+
+    let df = RpcTransferSource::new(&mock.url()).unwrap().fetch(BEARS, 0, None).await.unwrap();
+
+    for column in ["nonce", "gas_price", "gas_used", "cumulative_gas_used"] {
+        assert_eq!(df.column(column).unwrap().null_count(), df.height(), "{}", column);
+    }
+    assert!(df.column("gas").unwrap().utf8().unwrap().into_iter().all(|x| x == Some("")));
+}
@@ -0,0 +1,109 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{Datelike, TimeZone, Utc, Weekday};
+
+use conf::Config;
+use lib::{Error, Scheduler};
+
+const CONFIG: &str = r#"
+schedule:
+  - cron: "0 18 * * *"
+    command: "migration"
+    collection: "bears"
+  - name: "weekly bears"
+    cron: "0 18 * * MON"
+    command: "mint-activity"
+    collection: "bears"
+    lookback_days: 7
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+"#;
+
+#[test]
+fn next_due_gathers_the_jobs_of_the_next_run() {
+    let config = Config::from_yaml(CONFIG).unwrap();
+    let scheduler = Scheduler::from_config(&config).unwrap();
+    let names = |jobs: Vec<&lib::Job>| jobs.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
+
+    // sunday 2021-09-05 12:00
+    let (at, due) = scheduler.next_due(&Utc.with_ymd_and_hms(2021, 9, 5, 12, 0, 0).unwrap()).unwrap();
+    assert_eq!(at, Utc.with_ymd_and_hms(2021, 9, 5, 18, 0, 0).unwrap());
+    assert_eq!(names(due), vec!["bears migration"]);
+
+    let (at, due) = scheduler.next_due(&at).unwrap();
+    assert_eq!(at, Utc.with_ymd_and_hms(2021, 9, 6, 18, 0, 0).unwrap());
+    assert_eq!(names(due), vec!["bears migration", "weekly bears"]);
+}
+
+/// Next 7 runs of cron after sunday 2021-09-05 12:00
+fn upcoming(cron: &str) -> Vec<chrono::DateTime<Utc>> {
+    let mut config = Config::from_yaml(CONFIG).unwrap();
+    config.schedule[0].cron = String::from(cron);
+
+    config.schedule[0].schedule().unwrap().after(&Utc.with_ymd_and_hms(2021, 9, 5, 12, 0, 0).unwrap()).take(7).collect()
+}
+
+#[test]
+fn crontab_weekdays_count_from_sunday_0() {
+    let weekdays = |cron: &str| upcoming(cron).iter().map(|x| x.weekday()).collect::<Vec<_>>();
+
+    assert_eq!(upcoming("0 9 * * 1")[0], Utc.with_ymd_and_hms(2021, 9, 6, 9, 0, 0).unwrap());
+    assert_eq!(weekdays("0 9 * * 1"), vec![Weekday::Mon; 7]);
+    assert_eq!(weekdays("0 9 * * 0"), vec![Weekday::Sun; 7]);
+    assert_eq!(weekdays("0 9 * * 7"), vec![Weekday::Sun; 7]);
+    assert_eq!(weekdays("0 9 * * 6")[0], Weekday::Sat);
+    assert_eq!(weekdays("0 9 * * 1-5")[..5], [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri]);
+    assert_eq!(weekdays("0 9 * * 5-7")[..3], [Weekday::Fri, Weekday::Sat, Weekday::Sun]);
+    assert_eq!(weekdays("0 9 * * */2")[..4], [Weekday::Tue, Weekday::Thu, Weekday::Sat, Weekday::Sun]);
+    assert_eq!(weekdays("0 9 * * MON,3")[..2], [Weekday::Mon, Weekday::Wed]);
+}
+
+#[tokio::test]
+async fn failing_jobs_do_not_stop_the_schedule() {
+    let config = Config::from_yaml(r#"
+schedule:
+  - name: "fails"
+    cron: "* * * * * *"
+    command: "migration"
+    collection: "bears"
+  - name: "panics"
+    cron: "* * * * * *"
+    command: "export"
+    collection: "bears"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+"#).unwrap();
+    let scheduler = Scheduler::from_config(&config).unwrap();
+    let runs = Arc::new(Mutex::new(vec![]));
+
+    let shutdown = tokio::time::sleep(Duration::from_millis(2500));
+    scheduler.run(|job| {
+        let runs = runs.clone();
+        async move {
+            runs.lock().unwrap().push(job.name.clone());
+            match job.name.as_str() {
+                "fails" => Err(Error::MissingConfig { key: String::from("etherscan") }),
+                _ => panic!("job blew up"),
+            }
+        }
+    }, shutdown).await.unwrap();
+
+    let runs = runs.lock().unwrap();
+    assert!(runs.len() >= 4, "{:?}", runs);
+    assert_eq!(runs.iter().filter(|x| *x == "fails").count(), runs.iter().filter(|x| *x == "panics").count());
+}
+
+#[test]
+fn bad_cron_is_a_config_error() {
+    let mut config = Config::from_yaml(CONFIG).unwrap();
+    config.schedule[0].cron = String::from("every day");
+
+    assert!(matches!(Scheduler::from_config(&config), Err(Error::InvalidConfig { .. })));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use conf::Config;
+use lib::{refresh_cache, serve, EtherscanClient, TransferCache};
+use mock::MockEtherscan;
+use common::{BEARS, BEARS_FIXTURE};
+
+const CONFIG: &str = r#"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+"#;
+
+async fn get(base: &str, path: &str) -> (u16, Value) {
+    let response = reqwest::get(format!("{}{}", base, path)).await.unwrap();
+    (response.status().as_u16(), response.json().await.unwrap())
+}
+
+/// Polls until cond holds, panics after 5s
+async fn eventually<F: Fn() -> bool>(cond: F) {
+    for _ in 0..100 {
+        if cond() { return; }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("condition not met within 5s");
+}
+
+#[tokio::test]
+async fn stats_are_served_from_the_cache() {
+    let mock = MockEtherscan::start().await;
+    mock.load_fixture(BEARS, BEARS_FIXTURE);
+    mock.set_head(14003100);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(
+        listener,
+        Config::from_yaml(CONFIG).unwrap(),
+        EtherscanClient::new("test").base_url(&mock.base_url()),
+        TransferCache::open(":memory:").unwrap(),
+        async { stopped.await.unwrap_or_default() },
+    ));
+
+    // the first sync runs as the server starts, until then bears is 404
+    for _ in 0..100 {
+        if get(&base, "/collections/bears/holders").await.0 == 200 { break; }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    let synced = mock.calls();
+
+    let (status, body) = get(&base, "/collections").await;
+    assert_eq!(status, 200);
+    assert_eq!(body["collections"][0]["name"], "bears");
+
+    let (status, body) = get(&base, "/collections/bears/mint-activity?lookback=30").await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["title"], "Bears Deluxe 30D Mint Activity");
+    assert_eq!(body["total"], 6);
+    let daily: i64 = body["daily"].as_array().unwrap().iter().map(|x| x["mints"].as_i64().unwrap()).sum();
+    assert_eq!(daily, 6);
+
+    let (status, body) = get(&base, "/collections/Bears/migration").await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["remaining"], 6894);
+
+    let (status, body) = get(&base, "/collections/bears/holders?top=1").await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["top"].as_array().unwrap().len(), 1);
+    assert!(body["holders"].as_u64().unwrap() >= 1);
+
+    let (status, body) = get(&base, "/collections/bees/migration").await;
+    assert_eq!(status, 404);
+    assert!(body["error"].as_str().unwrap().contains("bees"), "{}", body);
+
+    assert_eq!(get(&base, "/collections/bears/mint-activity?lookback=365").await.0, 400);
+    assert_eq!(get(&base, "/collections/bears/holders?top=many").await.0, 400);
+
+    // requests read the stored rows, only the background sync calls etherscan
+    assert_eq!(mock.calls(), synced);
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn cache_is_refreshed_each_period() {
+    let mock = MockEtherscan::start().await;
+    mock.load_fixture(BEARS, BEARS_FIXTURE);
+    mock.set_head(13996510);
+
+    let config = Config::from_yaml(CONFIG).unwrap();
+    let cache = Arc::new(TransferCache::open(":memory:").unwrap());
+    let refresh = tokio::spawn(refresh_cache(
+        EtherscanClient::new("test").base_url(&mock.base_url()),
+        cache.clone(),
+        config.collections.clone(),
+        Duration::from_millis(100),
+    ));
+
+    eventually(|| cache.rows(BEARS).unwrap().len() == 4).await;
+
+    mock.set_head(14003100);
+    eventually(|| cache.rows(BEARS).unwrap().len() == 7).await;
+
+    refresh.abort();
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use lib::{holders, mint_act, CachedTransferSource, EtherscanClient, FileTransferSource, TransferCache, TransferSource};
+use mock::MockEtherscan;
+use common::{BEARS, BEARS_FIXTURE, mint_sums};
+
+#[tokio::test]
+async fn file_source_feeds_mint_act() {
+    let source = FileTransferSource::new(BEARS_FIXTURE);
+
+    let df = mint_act(&source, BEARS).await.unwrap();
+
+    assert_eq!(mint_sums(&df), vec![2, 3, 1]);
+}
+
+#[tokio::test]
+async fn holders_follow_the_last_transfer() {
+    let source = FileTransferSource::new(BEARS_FIXTURE);
+
+    let df = holders(&source.fetch(BEARS, 0, None).await.unwrap()).unwrap();
+
+    let tokens: Vec<u32> = df.column("tokens").unwrap().u32().unwrap().into_iter().map(|x| x.unwrap()).collect();
+    assert_eq!(tokens, vec![3, 2, 1]);
+    assert!(df.column("holder").unwrap().utf8().unwrap().into_iter().next().flatten().unwrap().starts_with("0x1db343"));
+}
+
+#[tokio::test]
+async fn sources_agree_on_block_range() {
+    let mock = MockEtherscan::start().await;
+    mock.load_fixture(BEARS, BEARS_FIXTURE);
+    mock.set_head(14003100);
+
+    let client = EtherscanClient::new("test").base_url(&mock.base_url());
+    let cached = CachedTransferSource::new(
+        EtherscanClient::new("test").base_url(&mock.base_url()),
+        TransferCache::open(":memory:").unwrap(),
+    );
+    let file = FileTransferSource::new(BEARS_FIXTURE);
+
+    let sources: Vec<&dyn TransferSource> = vec![&client, &cached, &file];
+    for source in sources {
+        let df = source.fetch(BEARS, 13996500, Some(13996530)).await.unwrap();
+        assert_eq!(df.height(), 4);
+    }
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use polars::prelude::*;
+use serde_json::json;
+
+use conf::Config;
+use lib::{find_collection, migration_vars, mint_vars, render_status, render_template_act, status_template};
+use lib::{Error, FileTransferSource, Target, MINT_ACTIVITY_TEMPLATE};
+use common::BEARS_FIXTURE;
+
+const CONFIG: &str = r#"
+templates:
+  mint-activity: "{{display_name}}: {{total}} minted"
+collections:
+  - name: "bears"
+    display_name: "Bears Deluxe"
+    address: "0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B"
+    max_supply: 6900
+    templates:
+      migration: |
+        {{display_name}} {{fixed progress 1}}% migrated, {{remaining}} to go
+  - name: "bees"
+    display_name: "Bees Deluxe"
+    address: "0x1c2CD50f9Efb463bDd2ec9E36772c14A8D1658B3"
+    max_supply: 5000
+"#;
+
+/// Daily mints on 2021-09-01 (18871), 09-03, 09-08 and 09-10
+fn daily() -> DataFrame {
+    let days = Series::new("timestamp", &[18871, 18873, 18878, 18880]).cast(&DataType::Date).unwrap();
+    let mints = Series::new("mint_sum", &[10u32, 40, 5, 20]);
+    DataFrame::new(vec![days, mints]).unwrap()
+}
+
+#[test]
+fn variables_cover_pace_rank_and_deltas() {
+    let vars = mint_vars(&daily()).unwrap();
+
+    assert_eq!(vars["total"], 75);
+    assert_eq!(vars["minted_today"], 20);
+    assert_eq!(vars["last_day"], "2021-09-10");
+    assert_eq!(vars["days"], 4);
+    assert_eq!(vars["rank"], 2);
+    assert_eq!(vars["deltas"], json!({ "day": 15, "week": -25 }));
+
+    let vars = migration_vars(vars, 103);
+    assert_eq!(vars["remaining"], 28);
+    assert_eq!(vars["eta_days"], 8);
+    assert_eq!(vars["eta"], "2021-09-18");
+
+    let status = render_status("migration", "{{fixed progress 2}}% {{signed deltas.day}} {{signed deltas.week}}", &json!(vars)).unwrap();
+    assert_eq!(status, "72.82% +15 -25");
+}
+
+#[test]
+fn templates_are_picked_and_checked() {
+    let config = Config::from_yaml(CONFIG).unwrap();
+    let bears = find_collection(&config.collections, "bears").unwrap();
+    let bees = find_collection(&config.collections, "bees").unwrap();
+
+    assert!(status_template(&config, Some(bears), "migration").unwrap().ends_with("to go\n"));
+    assert_eq!(status_template(&config, Some(bees), "mint-activity").unwrap(), "{{display_name}}: {{total}} minted");
+    assert_eq!(status_template(&Config::default(), None, "mint-activity").unwrap(), MINT_ACTIVITY_TEMPLATE);
+
+    let err = render_status("migration", "{{progres}}%", &json!({ "progress": 50.0 })).unwrap_err();
+    assert!(matches!(err, Error::Template { .. }), "{}", err);
+    assert!(render_status("migration", "{{#if progress}}", &json!({})).is_err());
+}
+
+#[tokio::test]
+async fn render_template_previews_against_the_source() {
+    let config = Config::from_yaml(CONFIG).unwrap();
+    let bears = find_collection(&config.collections, "bears").unwrap();
+    let source = FileTransferSource::new(BEARS_FIXTURE);
+
+    let status = render_template_act(&config, &source, "migration", &Target::Collection(bears), 0, None, false).await.unwrap();
+    assert_eq!(status, "Bears Deluxe 0.1% migrated, 6894 to go");
+
+    let status = render_template_act(&config, &source, "mint-activity", &Target::Collection(bears), 0, Some("<{{title}}> & {{rank}}"), false).await.unwrap();
+    assert_eq!(status, "<Bears Deluxe Historical Mint Activity> & 3");
+
+    let address = Target::Address("0x4BB33f6E69fd62cf3abbcC6F1F43b94A5D572C2B");
+    assert!(render_template_act(&config, &source, "migration", &address, 0, None, false).await.is_err());
+}
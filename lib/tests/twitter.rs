@@ -0,0 +1,35 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use egg_mode::media::ProgressInfo;
+use lib::wait_for_media;
+
+#[tokio::test]
+async fn media_is_polled_until_processed() {
+    let polls = Cell::new(0);
+    let started = Instant::now();
+
+    wait_for_media(Some(ProgressInfo::Pending(1)), Duration::from_secs(10), || {
+        polls.set(polls.get() + 1);
+        let progress = match polls.get() {
+            1 => ProgressInfo::InProgress(1),
+            _ => ProgressInfo::Success,
+        };
+        async move { Ok(Some(progress)) }
+    }).await.unwrap();
+
+    assert_eq!(polls.get(), 2);
+    assert!(started.elapsed() >= Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn media_processing_times_out() {
+    let started = Instant::now();
+
+    let err = wait_for_media(Some(ProgressInfo::Pending(30)), Duration::from_secs(5), || async {
+        Ok(Some(ProgressInfo::Success))
+    }).await.unwrap_err();
+
+    assert!(err.to_string().contains("media still processing"), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
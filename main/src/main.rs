@@ -1,33 +1,242 @@
-use lib::{erc721_mint_act, bear_mint_act, bee_mint_act, hive_mint_act};
-use conf::{parse_args, get_config, init_logger};
+use lib::{erc721_mint_act, migration_act, holders_act, export_act, history_act, render_template_act, run_pipeline, serve, find_collection};
+use lib::{collection_publishers, ledgered, run_job, CachedTransferSource, ChartOptions, DatasetTransferSource, EtherscanClient, Ledger, Outbox, Publisher, RpcTransferSource, Scheduler, Target, TransferCache, TransferSource};
+use lib::Error;
+use conf::{parse_args, get_config, init_logger, render_manpage, write_completions, Collection, Config, Shell, Verbosity};
 
+use std::io::Write;
+
+use chrono::Utc;
 use log::info;
 use clap::ArgMatches;
-use std::collections::BTreeMap;
 
-fn usage() {
-    println!("Usage: cargo run -- "); 
+/// Utility fn to resolve the --collection / --address pair of a subcommand
+fn target<'a>(config: &'a Config, args: &'a ArgMatches) -> lib::Result<Target<'a>> {
+    match (args.value_of("collection"), args.get_one::<String>("address")) {
+        (Some(name), _) => Ok(Target::Collection(find_collection(&config.collections, name)?)),
+        (None, Some(address)) => Ok(Target::Address(address)),
+        (None, None) => Err(Error::InvalidArgument {
+            name: String::from("address"),
+            value: String::new(),
+            reason: String::from("address or collection is required"),
+        }),
+    }
+}
+
+/// Utility fn to read the charts config, with --width / --height / --scale on top
+fn chart_options(config: &Config, args: &ArgMatches) -> lib::Result<ChartOptions> {
+    let mut options = ChartOptions::from_config(&config.charts)?;
+
+    if let Some(width) = args.get_one::<u64>("width") {
+        options = options.width(*width as usize);
+    }
+    if let Some(height) = args.get_one::<u64>("height") {
+        options = options.height(*height as usize);
+    }
+    if let Some(scale) = args.get_one::<f64>("scale") {
+        options = options.scale(*scale);
+    }
+    Ok(options)
+}
+
+/// Utility fn to build the transfer source picked by --input / --source. Only called by the
+/// subcommands that fetch transfers, so listing jobs needs no credentials and leaves the cache alone.
+fn transfer_source(config: &Config, cli_args: &ArgMatches) -> lib::Result<Box<dyn TransferSource>> {
+    let missing = |key: &str| Error::MissingConfig { key: String::from(key) };
+
+    Ok(match (cli_args.value_of("input"), cli_args.value_of("source").unwrap_or("etherscan")) {
+        (Some(input), _) => Box::new(DatasetTransferSource::open(input)?),
+        (None, "rpc") => Box::new(RpcTransferSource::from_config(config.rpc.as_ref().ok_or_else(|| missing("rpc"))?)?),
+        (None, _) => {
+            let es_client = EtherscanClient::from_config(config.etherscan.as_ref().ok_or_else(|| missing("etherscan"))?);
+            match &config.cache {
+                Some(cache) => Box::new(CachedTransferSource::new(es_client, TransferCache::from_config(cache)?)),
+                None => Box::new(es_client),
+            }
+        },
+    })
+}
+
+/// Utility fn to pick where --post / --dry-run statuses go: the outbox, or the publishers
+/// of the collection, recorded in the ledger when there is one. Empty when nothing is published.
+fn publishers(config: &Config, collection: Option<&Collection>, args: &ArgMatches) -> lib::Result<Vec<Box<dyn Publisher>>> {
+    match (args.is_present("dry_run"), args.is_present("post")) {
+        (true, _) => Ok(vec![Box::new(match args.value_of("outbox") {
+            Some(dir) => Outbox::new(dir),
+            None => Outbox::from_config(&config.output),
+        })]),
+        (false, true) => ledgered(config, collection_publishers(config, collection)?, args.is_present("force")),
+        (false, false) => Ok(vec![]),
+    }
+}
+
+/// Utility fn that resolves on ctrl-c, or on SIGTERM as sent by docker and systemd
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("ERR: SIGTERM handler cannot be installed");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    init_logger();
-    info!("main|starting");
-
     let cli_args: ArgMatches = parse_args();
-    let config_name = cli_args.value_of("conf").expect("ERR: cli [configuration] is invalid"); 
-    let config: BTreeMap<String, String> = get_config(config_name); 
-    let cmd = String::from(cli_args.value_of("cmd").expect("ERR: cli [cmd] is invalid"));
-
-    match cmd.as_str() {
-        "erc721_mint_act" => { erc721_mint_act(&config, cli_args,).await?; },
-        "migration" => { bear_mint_act(&config, cli_args,).await?; },
-        "bee_mint_act" => { bee_mint_act(&config, cli_args,).await?; }, 
-        "hive_mint_act" => { hive_mint_act(&config, cli_args,).await?; },
-        _ => { 
-            usage(); 
+
+    // generated from the cli definition alone, so they work without a config
+    match cli_args.subcommand() {
+        Some(("completions", args)) => {
+            let shell = *args.get_one::<Shell>("shell").expect("ERR: cli [shell] is required");
+            write_completions(shell, &mut std::io::stdout());
+            return Ok(());
+        },
+        Some(("manpage", _)) => {
+            std::io::stdout().write_all(render_manpage().as_bytes())?;
+            return Ok(());
+        },
+        _ => {},
+    }
+
+    let config_name = cli_args.value_of("conf").expect("ERR: cli [configuration] is invalid");
+    let mut config: Config = match get_config(config_name) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("ERR: {}", e);
             std::process::exit(1);
         },
+    };
+
+    if let Some(log_file) = cli_args.value_of("log_file") {
+        config.logging.file = String::from(log_file);
+    }
+    if let Err(e) = init_logger(&config.logging, Verbosity::from_args(&cli_args)) {
+        eprintln!("ERR: {}", e);
+        std::process::exit(1);
+    }
+    info!("main|starting");
+    info!("main|config={:?}", config);
+
+    let missing = |key: &str| Error::MissingConfig { key: String::from(key) };
+
+    // the ledger is local, no transfer source needed
+    if let Some(("history", args)) = cli_args.subcommand() {
+        let ledger = Ledger::from_config(config.ledger.as_ref().ok_or_else(|| missing("ledger"))?)?;
+        let limit = *args.get_one::<u64>("limit").expect("ERR: cli [limit] has a default");
+        history_act(&ledger, args.value_of("collection"), limit as usize)?;
+        return Ok(());
+    }
+
+    // the api reads the cache, synced from etherscan in the background, never --source or --input
+    if let Some(("serve", args)) = cli_args.subcommand() {
+        let cache = TransferCache::from_config(config.cache.as_ref().ok_or_else(|| missing("cache"))?)?;
+        let es_client = EtherscanClient::from_config(config.etherscan.as_ref().ok_or_else(|| missing("etherscan"))?);
+        let bind = match args.get_one::<std::net::SocketAddr>("bind") {
+            Some(x) => *x,
+            None => config.server.bind.parse().map_err(|_| Error::InvalidConfig {
+                key: String::from("server.bind"),
+                reason: String::from("is not an ip:port address"),
+            })?,
+        };
+        let listener = std::net::TcpListener::bind(bind).map_err(Error::Io)?;
+        println!("serving on http://{}", listener.local_addr()?);
+        serve(listener, config.clone(), es_client, cache, shutdown_signal()).await?;
+        return Ok(());
+    }
+
+    match cli_args.subcommand() {
+        Some(("mint-activity", args)) => {
+            let lookback = *args.get_one::<u64>("lookback").expect("ERR: cli [lookback] has a default");
+            let options = chart_options(&config, args)?;
+            let target = target(&config, args)?;
+            let collection = match target {
+                Target::Collection(x) => Some(x),
+                Target::Address(_) => None,
+            };
+            let publishers = publishers(&config, collection, args)?;
+            let source = transfer_source(&config, &cli_args)?;
+            erc721_mint_act(&config, source.as_ref(), &options, &target, lookback, args.value_of("output"), &publishers).await?;
+        },
+        Some(("migration", args)) => {
+            let collection = find_collection(&config.collections, args.value_of("collection").unwrap_or("bears"))?;
+            let publishers = publishers(&config, Some(collection), args)?;
+            let source = transfer_source(&config, &cli_args)?;
+            migration_act(&config, source.as_ref(), collection, &publishers).await?;
+        },
+        Some(("holders", args)) => {
+            let top = *args.get_one::<u64>("top").expect("ERR: cli [top] has a default");
+            let source = transfer_source(&config, &cli_args)?;
+            holders_act(source.as_ref(), &target(&config, args)?, top as usize).await?;
+        },
+        Some(("export", args)) => {
+            let source = transfer_source(&config, &cli_args)?;
+            export_act(source.as_ref(), &config.output, &target(&config, args)?, args.value_of("output"), args.value_of("format")).await?;
+        },
+        Some(("render-template", args)) => {
+            let command = args.value_of("command").expect("ERR: cli [command] is required");
+            let lookback = *args.get_one::<u64>("lookback").expect("ERR: cli [lookback] has a default");
+            let template = match args.value_of("template_file") {
+                Some(path) => Some(std::fs::read_to_string(path).map_err(Error::Io)?),
+                None => args.value_of("template").map(String::from),
+            };
+            let source = transfer_source(&config, &cli_args)?;
+            render_template_act(&config, source.as_ref(), command, &target(&config, args)?, lookback, template.as_deref(), args.is_present("vars")).await?;
+        },
+        Some(("pipeline", args)) => {
+            if args.is_present("list") {
+                for pipeline in &config.pipelines {
+                    let stages = pipeline.order().unwrap_or_default();
+                    let stages: Vec<String> = stages.iter().map(|x| format!("{} ({})", x.id, x.stage)).collect();
+                    println!("{} [{}]: {}", pipeline.name, pipeline.collection, stages.join(" -> "));
+                }
+                return Ok(());
+            }
+
+            let name = args.value_of("name").expect("ERR: cli [name] is required without --list");
+            let pipeline = config.pipelines.iter().find(|x| x.name == name).ok_or_else(|| Error::InvalidArgument {
+                name: String::from("pipeline"),
+                value: String::from(name),
+                reason: String::from("not in the pipelines section"),
+            })?;
+            let collection = find_collection(&config.collections, &pipeline.collection)?;
+            let publishers = publishers(&config, Some(collection), args)?;
+            let source = transfer_source(&config, &cli_args)?;
+            run_pipeline(&config, source.as_ref(), pipeline, &publishers).await?;
+        },
+        Some(("run", args)) => {
+            let scheduler = Scheduler::from_config(&config)?;
+
+            match (args.is_present("list"), args.value_of("job")) {
+                (true, _) => {
+                    let now = Utc::now();
+                    for job in scheduler.jobs() {
+                        let next = job.next_run(&now).map(|x| x.to_string()).unwrap_or_else(|| String::from("never"));
+                        println!("{}  {} ({})", next, job.name, job.config.cron);
+                    }
+                },
+                (false, Some(name)) => {
+                    let job = scheduler.jobs().iter().find(|x| x.name == name).ok_or_else(|| Error::InvalidArgument {
+                        name: String::from("job"),
+                        value: String::from(name),
+                        reason: String::from("not in the schedule section"),
+                    })?;
+                    let source = transfer_source(&config, &cli_args)?;
+                    run_job(&config, source.as_ref(), &job.config).await?;
+                },
+                (false, None) => {
+                    if scheduler.jobs().is_empty() {
+                        return Err(missing("schedule").into());
+                    }
+                    let source = transfer_source(&config, &cli_args)?;
+                    scheduler.run(|job| run_job(&config, source.as_ref(), &job.config), shutdown_signal()).await?;
+                },
+            }
+        },
+        _ => unreachable!("clap requires a known subcommand"),
     }
 
     info!("main|completed");
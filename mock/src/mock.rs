@@ -0,0 +1,194 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use serde_json::{json, Value};
+
+mod rpc;
+mod webhook;
+
+pub use rpc::{erc20_transfer_log, transfer_log, MockRpc, TRANSFER_TOPIC};
+pub use webhook::{MockWebhook, Recorded};
+
+/// Etherscan caps every query at 10,000 rows, including page x offset windows
+pub const ES_MAX_RESULTS: usize = 10_000;
+
+#[derive(Default)]
+struct State {
+    transfers: HashMap<String, Vec<Value>>,
+    scripted: VecDeque<(u16, String)>,
+    calls: usize,
+    last_query: HashMap<String, String>,
+    head: Option<u64>,
+}
+
+/// Local stand-in for the Etherscan api. Serves tokennfttx from fixture rows
+/// keyed by contract, honouring startblock/endblock/sort/page/offset and the 10k
+/// result cap, so pagination can be exercised offline.
+pub struct MockEtherscan {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockEtherscan {
+    /// Binds 127.0.0.1 on a free port and serves on the current tokio runtime
+    pub async fn start() -> MockEtherscan {
+        let state = Arc::new(Mutex::new(State::default()));
+        let svc_state = state.clone();
+
+        let make_svc = make_service_fn(move |_| {
+            let state = svc_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(&state, req)) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        MockEtherscan { addr, state }
+    }
+
+    /// Value for the etherscan.base_url config key
+    pub fn base_url(&self) -> String {
+        format!("http://{}/api", self.addr)
+    }
+
+    /// Replaces the transfer history of a contract
+    pub fn set_transfers(&self, contract: &str, rows: Vec<Value>) {
+        self.state.lock().unwrap().transfers.insert(contract.to_lowercase(), rows);
+    }
+
+    /// Sets the chain head. Rows above it are not served yet and confirmations
+    /// are reported as head - block + 1, like Etherscan does.
+    pub fn set_head(&self, block: u64) {
+        self.state.lock().unwrap().head = Some(block);
+    }
+
+    /// Loads the transfer history of a contract from a json array fixture
+    pub fn load_fixture(&self, contract: &str, path: &str) {
+        let data = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("fixture {} cannot be read: {}", path, e));
+        let rows: Vec<Value> = serde_json::from_str(&data).unwrap_or_else(|e| panic!("fixture {} is invalid: {}", path, e));
+        self.set_transfers(contract, rows);
+    }
+
+    /// Queues a canned response served ahead of the fixture data
+    pub fn push_response(&self, status: u16, body: &str) {
+        self.state.lock().unwrap().scripted.push_back((status, String::from(body)));
+    }
+
+    /// Number of requests served so far
+    pub fn calls(&self) -> usize {
+        self.state.lock().unwrap().calls
+    }
+
+    /// Query parameters of the most recent request
+    pub fn last_query(&self) -> HashMap<String, String> {
+        self.state.lock().unwrap().last_query.clone()
+    }
+}
+
+/// Builds a tokennfttx row with every field the Etherscan api returns
+pub fn transfer_row(contract: &str, block: u64, ts: u64, hash: &str, from: &str, to: &str, token_id: u32) -> Value {
+    json!({
+        "blockNumber": block.to_string(),
+        "timeStamp": ts.to_string(),
+        "hash": hash,
+        "nonce": "0",
+        "blockHash": format!("0x{:064x}", block),
+        "from": from,
+        "contractAddress": contract.to_lowercase(),
+        "to": to,
+        "tokenID": token_id.to_string(),
+        "tokenName": "Mock Deluxe",
+        "tokenSymbol": "MOCK",
+        "tokenDecimal": "0",
+        "transactionIndex": "0",
+        "gas": "150000",
+        "gasPrice": "50000000000",
+        "gasUsed": "120000",
+        "cumulativeGasUsed": "1200000",
+        "input": "deprecated",
+        "confirmations": "100",
+    })
+}
+
+fn handle(state: &Mutex<State>, req: Request<Body>) -> Response<Body> {
+    let mut state = state.lock().unwrap();
+    state.calls += 1;
+
+    let query: HashMap<String, String> = url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .into_owned()
+        .collect();
+    state.last_query = query.clone();
+
+    if let Some((status, body)) = state.scripted.pop_front() {
+        return respond(status, body);
+    }
+
+    let body = match query.get("action").map(|x| x.as_str()) {
+        Some("tokennfttx") => tokennfttx(&state, &query),
+        _ => json!({ "status": "0", "message": "NOTOK", "result": "Error! Missing Or invalid Action name" }),
+    };
+
+    respond(200, body.to_string())
+}
+
+fn tokennfttx(state: &State, query: &HashMap<String, String>) -> Value {
+    let param = |name: &str| query.get(name).and_then(|x| u64::from_str(x).ok());
+
+    let contract = query.get("contractaddress").map(|x| x.to_lowercase()).unwrap_or_default();
+    let start_block = param("startblock").unwrap_or(0);
+    let end_block = param("endblock").unwrap_or(u64::MAX).min(state.head.unwrap_or(u64::MAX));
+
+    let block = |row: &Value| row["blockNumber"].as_str().and_then(|x| u64::from_str(x).ok()).unwrap_or(0);
+
+    let mut rows: Vec<Value> = state.transfers.get(&contract)
+        .map(|x| x.iter().filter(|row| (start_block..=end_block).contains(&block(row))).cloned().collect())
+        .unwrap_or_default();
+    rows.sort_by_key(block);
+    if query.get("sort").map(|x| x.as_str()) == Some("desc") {
+        rows.reverse();
+    }
+
+    if let Some(head) = state.head {
+        for row in rows.iter_mut() {
+            row["confirmations"] = json!((head - block(row) + 1).to_string());
+        }
+    }
+
+    let rows: Vec<Value> = match (param("page"), param("offset")) {
+        (Some(page), Some(offset)) if page > 0 && offset > 0 => {
+            if (page * offset) as usize > ES_MAX_RESULTS {
+                return json!({
+                    "status": "0",
+                    "message": "NOTOK",
+                    "result": "Result window is too large, PageNo x Offset size must be less than or equal to 10000",
+                });
+            }
+            rows.into_iter().skip(((page - 1) * offset) as usize).take(offset as usize).collect()
+        },
+        _ => rows.into_iter().take(ES_MAX_RESULTS).collect(),
+    };
+
+    if rows.is_empty() {
+        return json!({ "status": "0", "message": "No transactions found", "result": [] });
+    }
+    json!({ "status": "1", "message": "OK", "result": rows })
+}
+
+fn respond(status: u16, body: String) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use serde_json::{json, Value};
+
+use crate::respond;
+
+/// keccak256("Transfer(address,address,uint256)"), topic0 of ERC20 and ERC721 transfers
+pub const TRANSFER_TOPIC: &str = "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+/// Selectors of name() and symbol()
+const NAME_SELECTOR: &str = "0x06fdde03";
+const SYMBOL_SELECTOR: &str = "0x95d89b41";
+
+#[derive(Default)]
+struct State {
+    logs: Vec<Value>,
+    timestamps: HashMap<u64, u64>,
+    metadata: HashMap<String, (String, String)>,
+    head: Option<u64>,
+    max_range: Option<u64>,
+    requests: Vec<(String, Value)>,
+}
+
+/// Local stand-in for a json-rpc node. Serves eth_blockNumber, eth_getLogs, eth_getBlockByNumber
+/// and the name() / symbol() eth_calls from logs added with add_log or load_fixture. A block
+/// range cap can be set with set_max_range to make eth_getLogs fail like providers do.
+pub struct MockRpc {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockRpc {
+    /// Binds 127.0.0.1 on a free port and serves on the current tokio runtime
+    pub async fn start() -> MockRpc {
+        let state = Arc::new(Mutex::new(State::default()));
+        let svc_state = state.clone();
+
+        let make_svc = make_service_fn(move |_| {
+            let state = svc_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(&state, req).await) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        MockRpc { addr, state }
+    }
+
+    /// Value for the rpc.url config key
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Sets the chain head, the highest log block when unset
+    pub fn set_head(&self, block: u64) {
+        self.state.lock().unwrap().head = Some(block);
+    }
+
+    /// Makes eth_getLogs fail on ranges spanning more than blocks blocks
+    pub fn set_max_range(&self, blocks: u64) {
+        self.state.lock().unwrap().max_range = Some(blocks);
+    }
+
+    /// Sets what name() and symbol() answer for a contract, others revert
+    pub fn set_metadata(&self, contract: &str, name: &str, symbol: &str) {
+        self.state.lock().unwrap().metadata.insert(contract.to_lowercase(), (String::from(name), String::from(symbol)));
+    }
+
+    /// Adds a log built by transfer_log or erc20_transfer_log, its block mined at timestamp
+    pub fn add_log(&self, log: Value, timestamp: u64) {
+        let block = quantity(&log["blockNumber"]).unwrap_or_else(|| panic!("log without blockNumber: {}", log));
+
+        let mut state = self.state.lock().unwrap();
+        state.timestamps.insert(block, timestamp);
+        state.logs.push(log);
+    }
+
+    /// Adds the Transfer logs of a tokennfttx json array fixture, the same data MockEtherscan serves
+    pub fn load_fixture(&self, contract: &str, path: &str) {
+        let data = std::fs::read_to_string(path).unwrap_or_else(|e| panic!("fixture {} cannot be read: {}", path, e));
+        let rows: Vec<Value> = serde_json::from_str(&data).unwrap_or_else(|e| panic!("fixture {} is invalid: {}", path, e));
+
+        let field = |row: &Value, name: &str| String::from(row[name].as_str().unwrap_or_else(|| panic!("fixture {} row without {}", path, name)));
+        let number = |row: &Value, name: &str| field(row, name).parse::<u64>().unwrap_or_else(|e| panic!("fixture {} {}: {}", path, name, e));
+
+        if let Some(row) = rows.first() {
+            self.set_metadata(contract, &field(row, "tokenName"), &field(row, "tokenSymbol"));
+        }
+        for row in &rows {
+            let mut log = transfer_log(
+                contract,
+                number(row, "blockNumber"),
+                &field(row, "hash"),
+                &field(row, "from"),
+                &field(row, "to"),
+                number(row, "tokenID"),
+            );
+            log["blockHash"] = json!(field(row, "blockHash"));
+            log["transactionIndex"] = json!(format!("0x{:x}", number(row, "transactionIndex")));
+            self.add_log(log, number(row, "timeStamp"));
+        }
+    }
+
+    /// Requests served so far as (method, params), oldest first
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// [fromBlock, toBlock] of every eth_getLogs served so far, failed ones included
+    pub fn log_ranges(&self) -> Vec<(u64, u64)> {
+        self.requests().iter()
+            .filter(|(method, _)| method == "eth_getLogs")
+            .filter_map(|(_, params)| Some((quantity(&params[0]["fromBlock"])?, quantity(&params[0]["toBlock"])?)))
+            .collect()
+    }
+}
+
+/// Builds an ERC721 Transfer log, tokenId indexed as the fourth topic
+pub fn transfer_log(contract: &str, block: u64, hash: &str, from: &str, to: &str, token_id: u64) -> Value {
+    json!({
+        "address": contract.to_lowercase(),
+        "topics": [TRANSFER_TOPIC, address_topic(from), address_topic(to), format!("0x{:064x}", token_id)],
+        "data": "0x",
+        "blockHash": format!("0x{:064x}", block),
+        "blockNumber": format!("0x{:x}", block),
+        "transactionHash": hash,
+        "transactionIndex": "0x0",
+        "logIndex": "0x0",
+        "removed": false,
+    })
+}
+
+/// Builds an ERC20 Transfer log, same topic0 but the amount is data rather than a topic
+pub fn erc20_transfer_log(contract: &str, block: u64, hash: &str, from: &str, to: &str, amount: u64) -> Value {
+    json!({
+        "address": contract.to_lowercase(),
+        "topics": [TRANSFER_TOPIC, address_topic(from), address_topic(to)],
+        "data": format!("0x{:064x}", amount),
+        "blockHash": format!("0x{:064x}", block),
+        "blockNumber": format!("0x{:x}", block),
+        "transactionHash": hash,
+        "transactionIndex": "0x0",
+        "logIndex": "0x0",
+        "removed": false,
+    })
+}
+
+async fn handle(state: &Mutex<State>, req: Request<Body>) -> Response<Body> {
+    let body = hyper::body::to_bytes(req.into_body()).await.map(|x| x.to_vec()).unwrap_or_default();
+
+    let request: Value = match serde_json::from_slice(&body) {
+        Ok(x) => x,
+        Err(e) => return respond(200, error(&Value::Null, -32700, &e.to_string()).to_string()),
+    };
+
+    let mut state = state.lock().unwrap();
+    let body = match request {
+        Value::Array(batch) => Value::Array(batch.iter().map(|x| call(&mut state, x)).collect()),
+        single => call(&mut state, &single),
+    };
+
+    respond(200, body.to_string())
+}
+
+fn call(state: &mut State, request: &Value) -> Value {
+    let id = &request["id"];
+    let method = request["method"].as_str().unwrap_or_default();
+    let params = &request["params"];
+    state.requests.push((String::from(method), params.clone()));
+
+    let head = state.head.unwrap_or_else(|| state.logs.iter().filter_map(|x| quantity(&x["blockNumber"])).max().unwrap_or(0));
+
+    match method {
+        "eth_blockNumber" => result(id, json!(format!("0x{:x}", head))),
+        "eth_getLogs" => get_logs(state, id, &params[0]),
+        "eth_getBlockByNumber" => match quantity(&params[0]) {
+            Some(block) if block <= head => result(id, block_json(block, state.timestamps.get(&block).copied().unwrap_or(0))),
+            _ => result(id, Value::Null),
+        },
+        "eth_call" => {
+            let to = params[0]["to"].as_str().unwrap_or_default().to_lowercase();
+            let data = params[0]["data"].as_str().or_else(|| params[0]["input"].as_str()).unwrap_or_default();
+
+            match (state.metadata.get(&to), data) {
+                (Some((name, _)), NAME_SELECTOR) => result(id, json!(abi_string(name))),
+                (Some((_, symbol)), SYMBOL_SELECTOR) => result(id, json!(abi_string(symbol))),
+                _ => error(id, 3, "execution reverted"),
+            }
+        },
+        _ => error(id, -32601, &format!("the method {} does not exist/is not available", method)),
+    }
+}
+
+fn get_logs(state: &State, id: &Value, filter: &Value) -> Value {
+    let (from_block, to_block) = match (quantity(&filter["fromBlock"]), quantity(&filter["toBlock"])) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return error(id, -32602, "fromBlock and toBlock must be block numbers"),
+    };
+
+    if let Some(max_range) = state.max_range {
+        if to_block.saturating_sub(from_block) + 1 > max_range {
+            return error(id, -32005, &format!("block range is too wide, max {} blocks", max_range));
+        }
+    }
+
+    // address and topics come as a single value or an array of alternatives
+    let any_of = |x: &Value| -> Vec<String> {
+        match x {
+            Value::String(s) => vec![s.to_lowercase()],
+            Value::Array(xs) => xs.iter().filter_map(|x| x.as_str()).map(|x| x.to_lowercase()).collect(),
+            _ => vec![],
+        }
+    };
+    let addresses = any_of(&filter["address"]);
+    let topic0 = any_of(&filter["topics"][0]);
+
+    let logs: Vec<Value> = state.logs.iter()
+        .filter(|x| quantity(&x["blockNumber"]).is_some_and(|b| b >= from_block && b <= to_block))
+        .filter(|x| addresses.is_empty() || addresses.contains(&x["address"].as_str().unwrap_or_default().to_lowercase()))
+        .filter(|x| topic0.is_empty() || topic0.contains(&x["topics"][0].as_str().unwrap_or_default().to_lowercase()))
+        .cloned()
+        .collect();
+
+    result(id, Value::Array(logs))
+}
+
+/// Block header with every field web3 requires, hashes derived from the number
+fn block_json(block: u64, timestamp: u64) -> Value {
+    let zero = format!("0x{:064x}", 0);
+    json!({
+        "number": format!("0x{:x}", block),
+        "hash": format!("0x{:064x}", block),
+        "parentHash": format!("0x{:064x}", block.saturating_sub(1)),
+        "sha3Uncles": zero,
+        "miner": format!("0x{:040x}", 0),
+        "stateRoot": zero,
+        "transactionsRoot": zero,
+        "receiptsRoot": zero,
+        "gasUsed": "0x0",
+        "gasLimit": "0x1c9c380",
+        "extraData": "0x",
+        "timestamp": format!("0x{:x}", timestamp),
+        "difficulty": "0x0",
+        "uncles": [],
+        "transactions": [],
+    })
+}
+
+fn result(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// Hex quantity such as "0x1f" as a number
+fn quantity(x: &Value) -> Option<u64> {
+    u64::from_str_radix(x.as_str()?.strip_prefix("0x")?, 16).ok()
+}
+
+fn address_topic(address: &str) -> String {
+    format!("0x{:0>64}", address.trim_start_matches("0x").to_lowercase())
+}
+
+/// abi encoding of a single string return value
+fn abi_string(s: &str) -> String {
+    let hex: String = s.bytes().map(|x| format!("{:02x}", x)).collect();
+    let padded = hex.len().div_ceil(64) * 64;
+    format!("0x{:064x}{:064x}{:0<width$}", 32, s.len(), hex, width = padded)
+}
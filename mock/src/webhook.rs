@@ -0,0 +1,108 @@
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use serde_json::Value;
+
+use crate::respond;
+
+/// A request received by MockWebhook
+#[derive(Clone, Debug)]
+pub struct Recorded {
+    pub method: String,
+    pub path: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Recorded {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(|x| x.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Body parsed as json, panics on anything else
+    pub fn json(&self) -> Value {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| panic!("{} {} body is not json: {}", self.method, self.path, e))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<Recorded>,
+    scripted: VecDeque<(u16, String)>,
+}
+
+/// Local stand-in for discord, telegram or any webhook publishers post to. Records every
+/// request and answers 200 {"ok":true}, unless a response was queued with push_response.
+pub struct MockWebhook {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockWebhook {
+    /// Binds 127.0.0.1 on a free port and serves on the current tokio runtime
+    pub async fn start() -> MockWebhook {
+        let state = Arc::new(Mutex::new(State::default()));
+        let svc_state = state.clone();
+
+        let make_svc = make_service_fn(move |_| {
+            let state = svc_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(record(&state, req).await) }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        MockWebhook { addr, state }
+    }
+
+    /// Url of path on the stand-in, e.g. url("/api/webhooks/1/token")
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Queues a canned response, served to the next request instead of the default
+    pub fn push_response(&self, status: u16, body: &str) {
+        self.state.lock().unwrap().scripted.push_back((status, String::from(body)));
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+async fn record(state: &Mutex<State>, req: Request<Body>) -> Response<Body> {
+    let (parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await.map(|x| x.to_vec()).unwrap_or_default();
+
+    let recorded = Recorded {
+        method: parts.method.to_string(),
+        path: parts.uri.path_and_query().map(|x| x.to_string()).unwrap_or_default(),
+        headers: parts.headers.iter()
+            .map(|(k, v)| (k.as_str().to_lowercase(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+            .collect(),
+        body,
+    };
+
+    let mut state = state.lock().unwrap();
+    state.requests.push(recorded);
+
+    match state.scripted.pop_front() {
+        Some((status, body)) => respond(status, body),
+        None => respond(200, String::from(r#"{"ok":true}"#)),
+    }
+}